@@ -0,0 +1,4233 @@
+//! Core of hcd: querying the HashiCorp releases API, resolving versions and platform builds,
+//! and downloading, verifying and extracting release artifacts.
+//!
+//! All network operations are async and take a caller-provided `reqwest::Client`.
+
+use clap::ValueEnum;
+use futures_util::StreamExt;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use lazy_static::lazy_static;
+use regex::Regex;
+use semver::{Version, VersionReq};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use pgp::composed::{Deserializable, DetachedSignature, SignedPublicKey};
+use pgp::types::KeyDetails;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
+use thiserror::Error;
+use time::format_description::well_known::Rfc3339;
+use time::{Date, OffsetDateTime};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::task;
+use tokio::process::Command as TokioCommand;
+use std::fs::File as StdFile;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::PathBuf;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, warn, Level, Metadata};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+
+const RELEASES_URL: &str = "https://api.releases.hashicorp.com/v1/";
+/// User-Agent sent with every request unless overridden, e.g. "hashicorp-downloader/0.2.0"
+pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+// HashiCorp's release signing key, see https://www.hashicorp.com/security
+const HASHICORP_GPG_KEY_URL: &str = "https://www.hashicorp.com/.well-known/pgp-key.txt";
+const HASHICORP_GPG_FINGERPRINT: &str = "C874011F0AB405110D02105534365D9472D7468F";
+
+// Whether stdout is reserved for a machine-readable document (--format json)
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Route informational messages to stderr, keeping stdout free for a machine-readable document.
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether informational messages currently go to stderr (see set_json_output)
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+// Shared display for concurrent downloads' progress bars
+static MULTI_PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Draw the progress bars of all subsequent downloads together, one line per download, so
+/// concurrent downloads don't overwrite each other's bars. Messages printed with `status!` and
+/// `suspend_progress` are kept clear of the bars.
+pub fn enable_multi_progress() {
+    let _ = MULTI_PROGRESS.set(MultiProgress::new());
+}
+
+/// Run `f` (e.g. printing to the terminal or prompting) with progress bars hidden
+pub fn suspend_progress<F: FnOnce() -> R, R>(f: F) -> R {
+    match MULTI_PROGRESS.get() {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
+
+#[doc(hidden)]
+pub fn print_status(args: std::fmt::Arguments) {
+    tracing::info!("{}", args);
+}
+
+/// Log an informational message at `info` level (see init_logging for where it ends up)
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        $crate::print_status(format_args!($($arg)*))
+    };
+}
+
+// --- Events ---
+
+// Whether structured events are written to stdout (see set_events)
+static EVENTS: AtomicBool = AtomicBool::new(false);
+
+// Minimum time between two download-progress events of the same download
+const PROGRESS_EVENT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Write an `Event` per step of a run to stdout as newline-delimited JSON (see `emit`). Combine
+/// with set_json_output so informational messages stay out of the stream.
+pub fn set_events(enabled: bool) {
+    EVENTS.store(enabled, Ordering::Relaxed);
+}
+
+/// A step of a download run, serialized as a JSON object whose `type` is the kebab-case variant
+/// name, e.g. `{"type":"resolve-started","product":"terraform","version":"latest"}`
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    /// Looking up the release of `product` matching `version`
+    ResolveStarted { product: &'a str, version: &'a str },
+    /// The release and platform build to download were found
+    Resolved { product: &'a str, version: &'a str, os: &'a str, arch: &'a str, url: &'a str },
+    /// `bytes` of `file` are on hand; `total` is null when the server doesn't declare the size
+    DownloadProgress { file: &'a str, bytes: u64, total: Option<u64> },
+    /// `file` is complete; `path` is null for a download extracted in memory, `skipped` means
+    /// an existing file was kept instead of downloading it, and `from_cache` that an offline run
+    /// used the file already in the download directory
+    DownloadComplete { file: &'a str, path: Option<&'a str>, bytes: u64, skipped: bool, from_cache: bool },
+    /// `files` executables from `file` were extracted into `dir`
+    ExtractComplete { file: &'a str, dir: &'a str, files: usize },
+    /// Processing `product` failed
+    Error { product: &'a str, message: &'a str },
+}
+
+/// Write `event` to stdout as one JSON line, flushed right away so a reading process sees it
+/// live. Does nothing unless enabled with set_events.
+pub fn emit(event: &Event) {
+    if !EVENTS.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(line) = serde_json::to_string(event) {
+        suspend_progress(|| {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+        });
+    }
+}
+
+// Emits the download-progress events of one download, at most one per PROGRESS_EVENT_INTERVAL
+// besides the final one
+struct ProgressEvents<'a> {
+    file: &'a str,
+    total: Option<u64>,
+    last: Option<std::time::Instant>,
+}
+
+impl<'a> ProgressEvents<'a> {
+    fn new(file: &'a str, total: Option<u64>) -> Self {
+        ProgressEvents { file, total, last: None }
+    }
+
+    fn update(&mut self, bytes: u64) {
+        if !EVENTS.load(Ordering::Relaxed) {
+            return;
+        }
+        let now = std::time::Instant::now();
+        let due = self.last.is_none_or(|last| now - last >= PROGRESS_EVENT_INTERVAL);
+        if due || self.total == Some(bytes) {
+            self.last = Some(now);
+            emit(&Event::DownloadProgress { file: self.file, bytes, total: self.total });
+        }
+    }
+}
+
+// --- Logging ---
+
+// Where log lines go: warnings and errors to stderr, everything else to stdout unless stdout
+// carries JSON output. Each line is written with the progress bars hidden.
+struct LogOutput;
+
+struct LogWriter {
+    stderr: bool,
+}
+
+impl std::io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        suspend_progress(|| {
+            if self.stderr {
+                std::io::stderr().write(buf)
+            } else {
+                std::io::stdout().write(buf)
+            }
+        })
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.stderr {
+            std::io::stderr().flush()
+        } else {
+            std::io::stdout().flush()
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogOutput {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> LogWriter {
+        LogWriter { stderr: json_output() }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> LogWriter {
+        LogWriter { stderr: json_output() || *meta.level() <= Level::WARN }
+    }
+}
+
+/// Install a global `tracing` subscriber printing hcd's messages as plain lines, the way the CLI
+/// shows them. `level` filters hcd's own events; other crates only log warnings and errors,
+/// or debug details too when `level` is `TRACE`. Does nothing if a subscriber is already set.
+pub fn init_logging(level: LevelFilter) {
+    let others = if level >= LevelFilter::TRACE { LevelFilter::DEBUG } else { LevelFilter::WARN };
+    let filter = Targets::new()
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_target("hcd", level)
+        .with_default(others.min(level));
+    let format = tracing_subscriber::fmt::layer()
+        .without_time()
+        .with_level(false)
+        .with_target(false)
+        .with_ansi(false)
+        .with_writer(LogOutput);
+    let _ = tracing_subscriber::registry().with(format).with(filter).try_init();
+}
+
+// --- Retry Logic ---
+
+/// How the delay between retries grows.
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackoffStrategy {
+    /// The base delay before every retry.
+    Fixed,
+    /// The base delay, doubled on each retry.
+    Exponential,
+    /// Like exponential, but a random 50-100% of it, so parallel clients don't retry in lockstep.
+    #[default]
+    ExponentialJitter,
+}
+
+/// How network requests are retried on connection errors and 5xx responses, and how long they
+/// wait out rate limiting (429 Too Many Requests).
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt.
+    pub retries: u32,
+    /// How the delay grows from one retry to the next.
+    pub strategy: BackoffStrategy,
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+    /// Longest delay before any retry, however far the strategy has grown it.
+    pub max_delay: Duration,
+    /// Longest total time a request waits for 429 responses to clear, following their
+    /// Retry-After header. These waits don't count against `retries`.
+    pub rate_limit_wait: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            strategy: BackoffStrategy::default(),
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            rate_limit_wait: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.delay_with(attempt, jitter())
+    }
+
+    // The delay before retry number `attempt` (from 0), given a random value in [0, 1) for the jitter
+    fn delay_with(&self, attempt: u32, random: f64) -> Duration {
+        let exponential = || self.base_delay.saturating_mul(1u32 << attempt.min(16)).min(self.max_delay);
+        match self.strategy {
+            BackoffStrategy::Fixed => self.base_delay.min(self.max_delay),
+            BackoffStrategy::Exponential => exponential(),
+            BackoffStrategy::ExponentialJitter => exponential().mul_f64(0.5 + 0.5 * random),
+        }
+    }
+}
+
+// Process-wide releases API base URL override, configured once from the command line
+static API_URL: OnceLock<String> = OnceLock::new();
+
+/// Override the releases API base URL (e.g. a mirror or a mock server) for all functions that
+/// don't take one explicitly. Only the first call takes effect.
+pub fn set_api_url(url: &str) -> Result<(), MyError> {
+    let _ = API_URL.set(normalize_api_url(url)?);
+    Ok(())
+}
+
+// Helper: the configured releases API base URL, or the public API
+fn api_url() -> &'static str {
+    API_URL.get().map(String::as_str).unwrap_or(RELEASES_URL)
+}
+
+// Helper: validate an API base URL and give it a trailing slash, so paths can be appended
+fn normalize_api_url(url: &str) -> Result<String, MyError> {
+    let invalid = |detail: &str| MyError::LogicError(format!("Invalid API URL '{}': {}", url, detail));
+    let parsed = reqwest::Url::parse(url).map_err(|e| invalid(&e.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid("expected an http or https URL"));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(invalid("must not contain a query or fragment"));
+    }
+    let mut normalized = parsed.to_string();
+    if !normalized.ends_with('/') {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
+// Process-wide bearer token for authenticated (enterprise/HCP) endpoints
+static AUTH_TOKEN: OnceLock<String> = OnceLock::new();
+
+/// Send `Authorization: Bearer <token>` with all releases API, artifact and checksum requests.
+/// Only the first call takes effect.
+pub fn set_auth_token(token: &str) {
+    let _ = AUTH_TOKEN.set(token.to_string());
+}
+
+// Helper: attach the configured bearer token, if any, to a request
+fn with_auth(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match &settings().auth_token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+// Helper: turn non-2xx responses into MyError::HttpStatus, explaining how to authenticate on 401/403
+fn ensure_success(response: reqwest::Response) -> Result<reqwest::Response, MyError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    warn_if_denied(status, response.url());
+    Err(http_status_error(status, response.url().as_str(), None))
+}
+
+// Helper: on 401/403, log how to authenticate
+fn warn_if_denied(status: reqwest::StatusCode, url: &url::Url) {
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        let hint = if settings().auth_token.is_some() {
+            "check that the token is valid and has access to this product"
+        } else {
+            "this endpoint requires authentication: pass --token or set HCP_TOKEN/HASHICORP_TOKEN"
+        };
+        warn!("Access denied ({}) for {}; {}.", status, url, hint);
+    }
+}
+
+// Helper: the error for a response with an unexpected status
+fn http_status_error(status: reqwest::StatusCode, url: &str, message: Option<String>) -> MyError {
+    MyError::HttpStatus { status: status.as_u16(), url: url.to_string(), message }
+}
+
+// Helper: decode a releases API response as JSON. An error status or a non-JSON body (e.g. an
+// HTML error page from a proxy) is reported with the server's own message instead of a parse error.
+async fn read_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, MyError> {
+    let status = response.status();
+    let url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let body = response.text().await?;
+
+    if !status.is_success() {
+        warn_if_denied(status, &url);
+        return Err(http_status_error(status, url.as_str(), server_message(&body)));
+    }
+    if !content_type.is_empty() && !content_type.contains("json") {
+        return Err(MyError::LogicError(format!(
+            "Expected JSON from {} but got '{}'{}",
+            url,
+            content_type,
+            server_message(&body).map(|m| format!(": {}", m)).unwrap_or_default()
+        )));
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+// Helper: a short human-readable message from an error body: the "message" field of a JSON
+// error object, the <title> of an HTML page, or the start of plain text
+fn server_message(body: &str) -> Option<String> {
+    const MAX_LEN: usize = 200;
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+    if let Ok(serde_json::Value::Object(obj)) = serde_json::from_str::<serde_json::Value>(body) {
+        if let Some(message) = ["message", "error"].iter().find_map(|k| obj.get(*k).and_then(|v| v.as_str())) {
+            return Some(message.to_string());
+        }
+    }
+    let lower = body.to_ascii_lowercase();
+    let text = match (lower.find("<title>"), lower.find("</title>")) {
+        (Some(start), Some(end)) if start + 7 <= end => &body[start + 7..end],
+        _ if lower.starts_with('<') => return None,
+        _ => body,
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    Some(match text.char_indices().nth(MAX_LEN) {
+        Some((i, _)) => format!("{}...", &text[..i]),
+        None => text,
+    })
+}
+
+// Process-wide retry policy, configured once from the command line
+static RETRY_POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+/// Set the retry policy used by all requests. Only the first call takes effect;
+/// without one, `RetryPolicy::default()` applies.
+pub fn set_retry_policy(policy: RetryPolicy) {
+    let _ = RETRY_POLICY.set(policy);
+}
+
+// Helper: a random value in [0, 1) for backoff jitter, seeded from std's randomized hasher keys
+fn jitter() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Helper: how long a 429 response asks to wait, from its Retry-After header in seconds or as an
+// HTTP date. Without a usable header, backs off like a failed request.
+fn retry_after(response: &reqwest::Response, policy: &RetryPolicy, attempt: u32) -> Duration {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER).and_then(|v| v.to_str().ok()).map(str::trim);
+    let requested = value.and_then(|value| match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let format = time::macros::format_description!(
+                "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+            );
+            let date = time::PrimitiveDateTime::parse(value, format).ok()?.assume_utc();
+            Some((date - OffsetDateTime::now_utc()).try_into().unwrap_or_default())
+        }
+    });
+    requested.unwrap_or_else(|| policy.delay(attempt))
+}
+
+// Send a request, retrying connection errors and 5xx responses with the policy's backoff, and
+// waiting out 429 responses for as long as their Retry-After asks (up to the policy's
+// rate_limit_wait in total). Other statuses (e.g. 404), and a 5xx or 429 once the retries or the
+// wait are used up, are returned for the caller to handle.
+async fn send_with_retry<F>(build: F) -> Result<reqwest::Response, MyError>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let settings = settings();
+    let policy = settings.retry_policy;
+    let mut attempt = 0;
+    let mut rate_limited = 0;
+    let mut waited = Duration::ZERO;
+    loop {
+        let (client, request) = build().build_split();
+        let request = request?;
+        if settings.offline {
+            return Err(offline_error(request.url().as_str()));
+        }
+        debug!("{} {}", request.method(), request.url());
+        let (reason, timeout) = match client.execute(request).await {
+            Ok(response) if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let delay = retry_after(&response, &policy, rate_limited);
+                if waited + delay > policy.rate_limit_wait {
+                    warn!(
+                        "Rate limited by {} (429 Too Many Requests); waiting {:.1}s more would exceed the rate limit wait, giving up.",
+                        response.url().host_str().unwrap_or_default(),
+                        delay.as_secs_f64()
+                    );
+                    return Ok(response);
+                }
+                warn!(
+                    "Rate limited by {} (429 Too Many Requests), retrying in {:.1}s...",
+                    response.url().host_str().unwrap_or_default(),
+                    delay.as_secs_f64()
+                );
+                tokio::time::sleep(delay).await;
+                waited += delay;
+                rate_limited += 1;
+                continue;
+            }
+            Ok(response) if response.status().is_server_error() && attempt < policy.retries => {
+                (format!("status {}", response.status()), None)
+            },
+            Ok(response) => return Ok(response),
+            Err(e) if e.is_timeout() => (e.to_string(), Some(e)),
+            Err(e) if e.is_connect() || e.is_request() => (e.to_string(), None),
+            Err(e) => return Err(e.into()),
+        };
+        if attempt >= policy.retries {
+            if let Some(e) = timeout {
+                return Err(MyError::Timeout(e));
+            }
+            return Err(MyError::LogicError(format!(
+                "Request failed after {} attempt(s): {}",
+                attempt + 1,
+                reason
+            )));
+        }
+        let delay = policy.delay(attempt);
+        warn!(
+            "Request failed ({}), retrying in {:.1}s ({}/{})...",
+            reason,
+            delay.as_secs_f64(),
+            attempt + 1,
+            policy.retries
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// --- Mirrors ---
+
+// Process-wide download mirrors, tried in order before the original URL
+static MIRRORS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Try these mirrors, in order, before the original URL for all subsequent downloads. A mirror
+/// replaces the scheme and host of the artifact URL and may add a path prefix, e.g.
+/// "https://mirror.example.com/hashicorp". Only the first call takes effect.
+pub fn set_mirrors(mirrors: &[String]) -> Result<(), MyError> {
+    let _ = MIRRORS.set(normalize_mirrors(mirrors)?);
+    Ok(())
+}
+
+// Helper: validate mirror URLs and drop their trailing slashes, so artifact paths can be appended
+fn normalize_mirrors(mirrors: &[String]) -> Result<Vec<String>, MyError> {
+    let mut normalized = Vec::new();
+    for mirror in mirrors {
+        let invalid = |detail: &str| MyError::LogicError(format!("Invalid mirror URL '{}': {}", mirror, detail));
+        let parsed = reqwest::Url::parse(mirror).map_err(|e| invalid(&e.to_string()))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(invalid("expected an http or https URL"));
+        }
+        if parsed.query().is_some() || parsed.fragment().is_some() {
+            return Err(invalid("must not contain a query or fragment"));
+        }
+        normalized.push(parsed.as_str().trim_end_matches('/').to_string());
+    }
+    Ok(normalized)
+}
+
+// Helper: the artifact URL rewritten onto each configured mirror, in order
+fn mirror_urls(url: &str) -> Result<Vec<String>, MyError> {
+    let settings = settings();
+    if settings.mirrors.is_empty() {
+        return Ok(Vec::new());
+    }
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| MyError::LogicError(format!("Invalid download URL '{}': {}", url, e)))?;
+    let tail = match parsed.query() {
+        Some(query) => format!("{}?{}", parsed.path(), query),
+        None => parsed.path().to_string(),
+    };
+    Ok(settings.mirrors.iter().map(|mirror| format!("{}{}", mirror, tail)).collect())
+}
+
+// Helper: the URLs to download an artifact from, in order: each mirror, then the original URL.
+// The flag marks the original, the only one that gets the auth token.
+fn download_sources(url: &str) -> Result<Vec<(String, bool)>, MyError> {
+    let mut sources: Vec<(String, bool)> = mirror_urls(url)?.into_iter().map(|u| (u, false)).collect();
+    sources.push((url.to_string(), true));
+    Ok(sources)
+}
+
+// --- Temporary Files ---
+
+// Process-wide scratch directory, set from --temp-dir
+static TEMP_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Create extraction scratch directories and the `.part` files of downloads in progress under
+/// `dir`. Only the first call takes effect; without one, extraction uses the system temp
+/// directory and partial downloads are kept next to their destination.
+pub fn set_temp_dir(dir: PathBuf) {
+    let _ = TEMP_DIR.set(dir);
+}
+
+/// The directory temporary files are created in: the running [`Downloader`]'s, the one from
+/// [`set_temp_dir`], or the system temp directory.
+pub fn temp_dir() -> PathBuf {
+    settings().temp_dir.clone().unwrap_or_else(std::env::temp_dir)
+}
+
+// Helper: create a fresh, uniquely named scratch directory under the temp dir
+async fn scratch_dir(prefix: &str) -> Result<ScratchDir, MyError> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let path = temp_dir().join(format!("{}-{}-{}", prefix, std::process::id(), millis));
+    tokio::fs::create_dir_all(&path).await?;
+    Ok(ScratchDir(path))
+}
+
+// Removes its directory when dropped, so scratch space doesn't accumulate on error paths
+struct ScratchDir(PathBuf);
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// `.part` files of the downloads currently in progress, for remove_partial_downloads
+static IN_FLIGHT: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Delete the `.part` files of all downloads still in progress, returning how many were removed.
+/// Meant for an interrupt handler that exits right after, as the downloads aren't stopped.
+pub fn remove_partial_downloads() -> usize {
+    let in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+    in_flight.iter().filter(|path| std::fs::remove_file(path).is_ok()).count()
+}
+
+/// How long a scratch directory must have been left untouched before [`clean_temp_dirs`] treats
+/// it as orphaned during a normal run.
+pub const STALE_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+// Helper: whether a directory name is one of hcd's scratch directories. `.hcd_extract_` is the
+// name older versions used for extraction directories created next to the extracted files.
+fn is_scratch_dir_name(name: &str) -> bool {
+    name.starts_with("hcd-extract-") || name.starts_with("hcd-checksum-") || name.starts_with(".hcd_extract_")
+}
+
+/// Remove the scratch directories that runs killed mid-extract or mid-download left directly in
+/// `dirs`, if they were last modified at least `min_age` ago (so ones a concurrent run is still
+/// using survive). Best-effort: unreadable directories are skipped. Returns the removed paths.
+pub fn clean_temp_dirs(dirs: &[PathBuf], min_age: Duration) -> Vec<PathBuf> {
+    let mut removed = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else { continue };
+        for entry in entries.flatten() {
+            if !entry.file_name().to_str().is_some_and(is_scratch_dir_name) {
+                continue;
+            }
+            // Entry metadata doesn't follow symlinks, so only real directories are removed
+            let Ok(meta) = entry.metadata() else { continue };
+            if !meta.is_dir() {
+                continue;
+            }
+            let age = meta.modified().ok().and_then(|m| SystemTime::now().duration_since(m).ok()).unwrap_or_default();
+            if age < min_age {
+                continue;
+            }
+            match std::fs::remove_dir_all(entry.path()) {
+                Ok(()) => removed.push(entry.path()),
+                Err(e) => warn!("Warning: could not remove {}: {}", entry.path().display(), e),
+            }
+        }
+    }
+    removed
+}
+
+// --- Bandwidth Limit ---
+
+// Process-wide download rate limit, shared by concurrent downloads
+static MAX_RATE: OnceLock<Arc<RateLimiter>> = OnceLock::new();
+
+/// Cap the combined speed of all subsequent downloads at `bytes_per_sec`. Only the first call
+/// takes effect; without one, downloads run at full speed.
+pub fn set_max_rate(bytes_per_sec: u64) {
+    if let Some(limiter) = RateLimiter::new(bytes_per_sec) {
+        let _ = MAX_RATE.set(limiter);
+    }
+}
+
+// Paces downloads on a virtual clock: each chunk reserves the time it takes at the limit, and
+// the download waits until its reservation ends. A connection slower than the limit never waits.
+#[derive(Debug)]
+struct RateLimiter {
+    bytes_per_sec: u64,
+    next: std::sync::Mutex<Option<tokio::time::Instant>>,
+}
+
+impl RateLimiter {
+    // A limiter shared by everything it's cloned into, or none for an unlimited rate of 0
+    fn new(bytes_per_sec: u64) -> Option<Arc<RateLimiter>> {
+        (bytes_per_sec > 0).then(|| Arc::new(RateLimiter { bytes_per_sec, next: std::sync::Mutex::new(None) }))
+    }
+
+    async fn consume(&self, bytes: usize) {
+        let until = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = tokio::time::Instant::now();
+            let start = next.filter(|t| *t > now).unwrap_or(now);
+            let end = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+            *next = Some(end);
+            end
+        };
+        tokio::time::sleep_until(until).await;
+    }
+}
+
+// Helper: wait as needed to keep downloads under the configured rate limit
+async fn throttle(bytes: usize) {
+    if let Some(limiter) = &settings().max_rate {
+        limiter.consume(bytes).await;
+    }
+}
+
+// --- Response Cache ---
+
+/// On-disk cache for the product list and release metadata, so repeated runs don't refetch them.
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    /// Directory holding the cached responses.
+    pub dir: PathBuf,
+    /// How long a cached response is used before it's fetched again.
+    pub ttl: Duration,
+    /// Ignore cached responses, but still store the fresh ones.
+    pub refresh: bool,
+}
+
+// Process-wide default cache policy, configured once from the command line; without one nothing
+// is cached
+static CACHE_POLICY: OnceLock<CachePolicy> = OnceLock::new();
+
+/// Cache API responses on disk for all subsequent requests (a `Downloader` can override it with
+/// [`DownloaderBuilder::cache_policy`]). Only the first call takes effect.
+pub fn set_cache_policy(policy: CachePolicy) {
+    let _ = CACHE_POLICY.set(policy);
+}
+
+// Whether network requests are refused (--offline)
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Never touch the network: API responses come only from the on-disk cache, whatever their age,
+/// and downloads only succeed for files that are already present. Any other request fails.
+pub fn set_offline(enabled: bool) {
+    OFFLINE.store(enabled, Ordering::Relaxed);
+}
+
+// Helper: the error for a request that offline mode refuses
+fn offline_error(url: &str) -> MyError {
+    MyError::LogicError(format!("Offline mode: refusing to request {}.", url))
+}
+
+// --- Request Settings ---
+
+// The settings requests are made with. A Downloader carries its own (see DownloaderBuilder);
+// other callers get the process-wide defaults from the set_* functions.
+#[derive(Clone, Default)]
+struct Settings {
+    auth_token: Option<String>,
+    retry_policy: RetryPolicy,
+    mirrors: Vec<String>,
+    temp_dir: Option<PathBuf>,
+    max_rate: Option<Arc<RateLimiter>>,
+    offline: bool,
+    cache_policy: Option<CachePolicy>,
+}
+
+impl Settings {
+    fn process_defaults() -> Settings {
+        Settings {
+            auth_token: AUTH_TOKEN.get().cloned(),
+            retry_policy: RETRY_POLICY.get().copied().unwrap_or_default(),
+            mirrors: MIRRORS.get().cloned().unwrap_or_default(),
+            temp_dir: TEMP_DIR.get().cloned(),
+            max_rate: MAX_RATE.get().cloned(),
+            offline: OFFLINE.load(Ordering::Relaxed),
+            cache_policy: CACHE_POLICY.get().cloned(),
+        }
+    }
+}
+
+// Leaves the token out, so it can't end up in logs
+impl std::fmt::Debug for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Settings")
+            .field("auth_token", &self.auth_token.as_ref().map(|_| "<redacted>"))
+            .field("retry_policy", &self.retry_policy)
+            .field("mirrors", &self.mirrors)
+            .field("temp_dir", &self.temp_dir)
+            .field("max_rate", &self.max_rate.as_ref().map(|limiter| limiter.bytes_per_sec))
+            .field("offline", &self.offline)
+            .field("cache_policy", &self.cache_policy)
+            .finish()
+    }
+}
+
+tokio::task_local! {
+    // The settings of the Downloader whose request is running on this task
+    static SETTINGS: Arc<Settings>;
+}
+
+// Helper: the settings for a request made now: the running Downloader's, or the process-wide ones
+fn settings() -> Arc<Settings> {
+    SETTINGS.try_with(Arc::clone).unwrap_or_else(|_| Arc::new(Settings::process_defaults()))
+}
+
+// A cached API response, with the URL it came from and when it was fetched (seconds since the epoch)
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    fetched_at: u64,
+    data: serde_json::Value,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Helper: GET a JSON document from the releases API, served from the cache while it's fresh.
+// `key` names the cache file; the stored URL guards against a different API base URL.
+async fn get_json_cached<T, F>(url: &str, key: &str, build: F) -> Result<T, MyError>
+where
+    T: DeserializeOwned,
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    cached_json(url, key, || async move { read_json(send_with_retry(build).await?).await }).await
+}
+
+// Helper: like get_json_cached, but with the document fetched by `fetch` on a cache miss, e.g.
+// when it takes more than one request
+async fn cached_json<T, F, Fut>(url: &str, key: &str, fetch: F) -> Result<T, MyError>
+where
+    T: DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<serde_json::Value, MyError>>,
+{
+    let settings = settings();
+    let policy = settings.cache_policy.as_ref();
+    let path = policy.map(|p| {
+        let key: String = key.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+        p.dir.join(format!("{}.json", key))
+    });
+
+    if settings.offline {
+        let cached = match &path {
+            Some(path) => read_cache(path, url, None).await,
+            None => None,
+        };
+        let data = cached.ok_or_else(|| MyError::LogicError(format!(
+            "Offline mode: no cached response for {}; run once without --offline to populate the cache.",
+            url
+        )))?;
+        return Ok(serde_json::from_value(data)?);
+    }
+
+    if let (Some(policy), Some(path)) = (policy, &path) {
+        if !policy.refresh {
+            if let Some(data) = read_cache(path, url, Some(policy.ttl)).await {
+                debug!("Using cached response for {} from {}", url, path.display());
+                return Ok(serde_json::from_value(data)?);
+            }
+        }
+    }
+
+    let data = fetch().await?;
+    if let Some(path) = &path {
+        if let Err(e) = write_cache(path, url, &data).await {
+            debug!("Failed to write cache file {}: {}", path.display(), e);
+        }
+    }
+    Ok(serde_json::from_value(data)?)
+}
+
+// Helper: a cached response for `url` that is younger than `ttl` (of any age without one), if
+// there is one
+async fn read_cache(path: &Path, url: &str, ttl: Option<Duration>) -> Option<serde_json::Value> {
+    let bytes = tokio::fs::read(path).await.ok()?;
+    let entry: CacheEntry = serde_json::from_slice(&bytes).ok()?;
+    let age = unix_now().saturating_sub(entry.fetched_at);
+    (entry.url == url && ttl.is_none_or(|ttl| age < ttl.as_secs())).then_some(entry.data)
+}
+
+// Helper: store a response in the cache, through a temporary file so concurrent readers never
+// see a partial entry
+async fn write_cache(path: &Path, url: &str, data: &serde_json::Value) -> Result<(), MyError> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let entry = CacheEntry { url: url.to_string(), fetched_at: unix_now(), data: data.clone() };
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    tokio::fs::write(&tmp, serde_json::to_vec(&entry)?).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+// --- Product List Logic ---
+
+/// Fetch the names of all products available under a license class
+pub async fn get_all_products(client: &reqwest::Client, license_class: &str) -> Result<Vec<String>, MyError> {
+    fetch_products(client, api_url(), license_class).await
+}
+
+/// Fetch all products available under a license class along with their latest supported stable
+/// version, looking up up to `jobs` products at once. This needs a request per product; use
+/// `get_all_products` when the names are enough.
+pub async fn get_products_detailed(client: &reqwest::Client, license_class: &str, jobs: usize) -> Result<Vec<ProductInfo>, MyError> {
+    let downloader = Downloader::builder().client(client.clone()).license_class(license_class).build()?;
+    let products = downloader.products().await?;
+    Ok(downloader.describe_products(products, jobs).await)
+}
+
+async fn fetch_products(client: &reqwest::Client, base_url: &str, license_class: &str) -> Result<Vec<String>, MyError> {
+    let url = format!("{}products?license_class={}", base_url, license_class);
+    status!("Fetching product list from API: {}", url);
+
+    let key = format!("products-{}", license_class);
+    let products: Vec<String> = cached_json(&url, &key, || fetch_product_pages(client, &url)).await?;
+    
+    Ok(products)
+}
+
+// Helper: fetch the product list as one JSON array, following pagination should the API add it:
+// a Link header with rel="next", or an object page such as {"products": [...], "next_cursor": "..."}
+// whose cursor is passed back as `after`. A plain array is the complete list.
+async fn fetch_product_pages(client: &reqwest::Client, url: &str) -> Result<serde_json::Value, MyError> {
+    let mut products = Vec::new();
+    let mut requested = HashSet::new();
+    let mut next = Some(url.to_string());
+    while let Some(page_url) = next.take() {
+        if !requested.insert(page_url.clone()) {
+            return Err(MyError::LogicError(format!("Product list pagination loops back to {}", page_url)));
+        }
+        let response = send_with_retry(|| {
+            with_auth(client.get(&page_url)).header("Accept", "application/vnd+hashicorp.releases-api.v1+json")
+        })
+        .await?;
+        let link = next_link(response.url(), response.headers());
+        let (items, cursor) = match read_json(response).await? {
+            serde_json::Value::Array(items) => (items, None),
+            serde_json::Value::Object(mut page) => {
+                let items = match page.remove("products") {
+                    Some(serde_json::Value::Array(items)) => items,
+                    _ => return Err(MyError::LogicError(format!("Unexpected product list page from {}", page_url))),
+                };
+                let cursor = page.get("next_cursor").and_then(|c| c.as_str()).filter(|c| !c.is_empty()).map(str::to_string);
+                (items, cursor)
+            }
+            _ => return Err(MyError::LogicError(format!("Unexpected product list from {}", page_url))),
+        };
+        products.extend(items);
+        next = match (link, cursor) {
+            (Some(link), _) => Some(link),
+            (None, Some(cursor)) => {
+                let mut next_url = reqwest::Url::parse(url).map_err(|e| MyError::LogicError(format!("Invalid URL {}: {}", url, e)))?;
+                next_url.query_pairs_mut().append_pair("after", &cursor);
+                Some(next_url.into())
+            }
+            (None, None) => None,
+        };
+        if let Some(next_url) = &next {
+            debug!("Fetching the next product list page: {}", next_url);
+        }
+    }
+    Ok(serde_json::Value::Array(products))
+}
+
+// Helper: the rel="next" target of a response's Link header, resolved against the response URL
+fn next_link(url: &reqwest::Url, headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get_all(reqwest::header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().split_once(';')?;
+            let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+            let is_next = params.split(';').any(|param| match param.split_once('=') {
+                Some((name, rel)) if name.trim().eq_ignore_ascii_case("rel") => {
+                    rel.trim().trim_matches('"').split_whitespace().any(|rel| rel.eq_ignore_ascii_case("next"))
+                }
+                _ => false,
+            });
+            if is_next { url.join(target).ok().map(String::from) } else { None }
+        })
+}
+
+
+// --- Data Models (Structs) ---
+
+/// Support status of a release
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Status {
+    pub state: ReleaseState,
+}
+
+/// Support state of a release; states this crate doesn't know are kept verbatim in `Other`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseState {
+    Supported,
+    Unsupported,
+    Withdrawn,
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl ReleaseState {
+    /// The state as the releases API spells it
+    pub fn as_str(&self) -> &str {
+        match self {
+            ReleaseState::Supported => "supported",
+            ReleaseState::Unsupported => "unsupported",
+            ReleaseState::Withdrawn => "withdrawn",
+            ReleaseState::Other(state) => state,
+        }
+    }
+}
+
+/// A product with its latest supported stable version, see `get_products_detailed`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProductInfo {
+    pub name: String,
+    /// `None` when the product has no supported stable release or it couldn't be looked up
+    pub latest_version: Option<String>,
+}
+
+/// A downloadable artifact of a release for one platform
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Build {
+    pub arch: String,
+    pub os: String,
+    pub url: String,
+}
+
+/// A product release as returned by the releases API
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Release {
+    pub version: String,
+    pub status: Status,
+    pub builds: Vec<Build>,
+    pub is_prerelease: bool,
+    #[serde(default, alias = "url_sha256sums")]
+    pub url_shasums: Option<String>,
+    #[serde(default)]
+    pub url_shasums_signatures: Vec<String>,
+    /// When the release was published, as an RFC 3339 timestamp (see `created`)
+    #[serde(default)]
+    pub timestamp_created: Option<String>,
+}
+
+impl Release {
+    /// When the release was published, if the API reported a valid timestamp
+    pub fn created(&self) -> Option<OffsetDateTime> {
+        self.timestamp_created.as_deref().and_then(|t| OffsetDateTime::parse(t, &Rfc3339).ok())
+    }
+
+    /// The distinct "os/arch" platforms the release ships builds for, in API order
+    pub fn platforms(&self) -> Vec<String> {
+        build_platforms(&self.builds)
+    }
+}
+
+/// The distinct "os/arch" platforms of `builds`, in order (e.g. for the result of `list_builds`)
+pub fn build_platforms(builds: &[Build]) -> Vec<String> {
+    let mut platforms: Vec<String> = Vec::new();
+    for build in builds {
+        let platform = format!("{}/{}", build.os, build.arch);
+        if !platforms.contains(&platform) {
+            platforms.push(platform);
+        }
+    }
+    platforms
+}
+
+// --- Platform Mappings ---
+
+lazy_static! {
+    static ref ARCH_MAPPING: HashMap<&'static str, &'static str> = {
+        let mut m = HashMap::new();
+        m.insert("x86_64", "amd64");
+        m.insert("aarch64", "arm64");
+        // HashiCorp's 32-bit "arm" builds target ARMv6, so they also cover ARMv7 hosts
+        m.insert("arm", "arm");
+        m.insert("x86", "386");
+        m.insert("i686", "386");
+        m.insert("powerpc64le", "ppc64le");
+        m.insert("s390x", "s390x");
+        m
+    };
+    static ref OS_MAPPING: HashMap<&'static str, &'static str> = {
+        let mut m = HashMap::new();
+        m.insert("linux", "linux");
+        m.insert("macos", "darwin");
+        m.insert("windows", "windows");
+        m.insert("freebsd", "freebsd");
+        m.insert("openbsd", "openbsd");
+        m.insert("netbsd", "netbsd");
+        m.insert("solaris", "solaris");
+        // illumos distributions run HashiCorp's Solaris builds
+        m.insert("illumos", "solaris");
+        m
+    };
+    static ref PRODUCT_ALIASES: HashMap<&'static str, &'static str> = {
+        let mut m = HashMap::new();
+        m.insert("tf", "terraform");
+        m.insert("vlt", "vault");
+        m.insert("pkr", "packer");
+        m.insert("nmd", "nomad");
+        m
+    };
+}
+
+// --- Custom Error Handling ---
+
+/// Errors returned by all library operations
+#[derive(Error, Debug)]
+pub enum MyError {
+    #[error("Network request error")]
+    Request(#[source] reqwest::Error),
+    #[error("Request timed out: {0}")]
+    Timeout(#[source] reqwest::Error),
+    #[error("JSON processing error")]
+    Json(#[from] serde_json::Error),
+    #[error("HTTP {status} from {url}{}", message.as_deref().map(|m| format!(": {}", m)).unwrap_or_default())]
+    HttpStatus { status: u16, url: String, message: Option<String> },
+    #[error("Logic error: {0}")]
+    LogicError(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<reqwest::Error> for MyError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            MyError::Timeout(e)
+        } else {
+            MyError::Request(e)
+        }
+    }
+}
+
+/// How to handle a destination file that already exists, for both downloads and extraction.
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverwritePolicy {
+    /// Keep the existing file and skip writing.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+    /// Write to a new name with a numeric suffix (e.g. `terraform-1.exe`).
+    Rename,
+    /// Fail with an error.
+    Error,
+}
+
+/// Artifact format to prefer when a release publishes several builds for one platform
+/// (e.g. a ZIP and an MSI for windows/amd64).
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactFormat {
+    Zip,
+    Msi,
+}
+
+impl ArtifactFormat {
+    /// The file extension of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArtifactFormat::Zip => "zip",
+            ArtifactFormat::Msi => "msi",
+        }
+    }
+
+    /// Whether a build's artifact is in this format, judged by its URL's extension
+    pub fn matches(&self, build: &Build) -> bool {
+        let path = Path::new(&build.url);
+        match self {
+            ArtifactFormat::Zip => has_zip_ext(path),
+            ArtifactFormat::Msi => has_msi_ext(path),
+        }
+    }
+}
+
+// --- Path Reporting ---
+
+/// How file paths are shown in messages
+#[derive(Debug, Clone)]
+pub enum PathStyle {
+    /// As constructed from the target directory
+    AsIs,
+    /// Canonical absolute paths
+    Absolute,
+    /// Relative to the given absolute base directory
+    RelativeTo(PathBuf),
+}
+
+impl PathStyle {
+    /// Render a path for display in this style
+    pub fn render(&self, p: &Path) -> String {
+        match self {
+            PathStyle::AsIs => p.display().to_string(),
+            PathStyle::Absolute => absolutize(p).display().to_string(),
+            PathStyle::RelativeTo(base) => relative_path(&absolutize(p), base).display().to_string(),
+        }
+    }
+}
+
+/// Canonicalize a path; for paths that don't exist (yet), canonicalize the
+/// nearest existing ancestor and re-append the remaining components
+pub fn absolutize(p: &Path) -> PathBuf {
+    if let Ok(canonical) = std::fs::canonicalize(p) {
+        return canonical;
+    }
+    let joined = if p.is_absolute() {
+        p.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(p)
+    };
+    let mut normalized = PathBuf::new();
+    for comp in joined.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => { normalized.pop(); }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    let mut existing = normalized.clone();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match existing.file_name() {
+            Some(name) => rest.push(name.to_os_string()),
+            None => return normalized,
+        }
+        existing.pop();
+    }
+    let mut result = std::fs::canonicalize(&existing).unwrap_or(existing);
+    result.extend(rest.iter().rev());
+    result
+}
+
+// Helper: express an absolute path relative to an absolute base directory
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path_comps: Vec<Component> = path.components().collect();
+    let base_comps: Vec<Component> = base.components().collect();
+    // Paths on different roots (e.g. Windows drives) cannot be made relative
+    if path_comps.first() != base_comps.first() {
+        return path.to_path_buf();
+    }
+    let common = path_comps.iter().zip(&base_comps).take_while(|(a, b)| a == b).count();
+    let mut result = PathBuf::new();
+    for _ in common..base_comps.len() {
+        result.push("..");
+    }
+    for comp in &path_comps[common..] {
+        result.push(comp.as_os_str());
+    }
+    if result.as_os_str().is_empty() {
+        result.push(".");
+    }
+    result
+}
+
+
+// --- Download Logic ---
+
+// Helper: create a progress bar for a download of `len` bytes, or a byte-counting spinner when the
+// length is unknown. Returns a hidden bar when progress output is disabled. Alongside other
+// downloads (see enable_multi_progress), each bar is labelled with the file name.
+fn download_progress(len: Option<u64>, enabled: bool, name: &str) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let label = if MULTI_PROGRESS.get().is_some() { "{prefix} " } else { "" };
+    let pb = match len {
+        Some(len) => {
+            let pb = ProgressBar::new(len);
+            pb.set_style(
+                ProgressStyle::with_template(&format!("{}{{bar:40.cyan/blue}} {{bytes}}/{{total_bytes}} ({{bytes_per_sec}}, {{eta}})", label))
+                    .unwrap_or_else(|_| ProgressStyle::default_bar())
+                    .progress_chars("=> "),
+            );
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::with_template(&format!("{}{{spinner}} {{bytes}} ({{bytes_per_sec}})", label))
+                    .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+            );
+            pb
+        }
+    };
+    pb.set_prefix(name.to_string());
+    match MULTI_PROGRESS.get() {
+        Some(multi) => multi.add(pb),
+        None => pb,
+    }
+}
+
+/// Derive the artifact filename from its download URL: the last non-empty path segment, ignoring
+/// any query string or fragment (e.g. the token of a signed URL)
+pub fn url_filename(url: &str) -> Result<String, MyError> {
+    let parsed = url::Url::parse(url)
+        .map_err(|e| MyError::LogicError(format!("Invalid download URL '{}': {}", url, e)))?;
+    let name = parsed
+        .path_segments()
+        .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+        .map(str::to_string)
+        .ok_or_else(|| MyError::LogicError("Could not extract filename from URL.".to_string()))?;
+    validate_file_name(&name)?;
+    Ok(name)
+}
+
+/// Check that `name` is a plain file name, so joining it onto a directory can't leave that
+/// directory: not empty, "." or "..", and free of path separators and NUL bytes
+pub fn validate_file_name(name: &str) -> Result<(), MyError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        return Err(MyError::LogicError(format!("Unsafe file name '{}'.", name.escape_debug())));
+    }
+    Ok(())
+}
+
+/// What a call to `download_file` or `download_to` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadOutcome {
+    /// Where the artifact is, which differs from the requested name with `OverwritePolicy::Rename`
+    /// (`STDOUT_TARGET` when it was streamed to stdout)
+    pub path: PathBuf,
+    /// Size of the file in bytes
+    pub bytes: u64,
+    /// An existing file was kept per the overwrite policy and nothing was downloaded
+    pub skipped: bool,
+    /// An existing file was reused in offline mode (see `set_offline`)
+    pub from_cache: bool,
+}
+
+/// The `target_dir` that makes `download_file` write the artifact to stdout instead of a file
+pub const STDOUT_TARGET: &str = "-";
+
+/// Download `url` into `target_dir`, resuming from a leftover `.part` file when `resume` is set.
+/// With `STDOUT_TARGET`, the body is streamed to stdout instead, whatever the overwrite policy.
+pub async fn download_file(client: &reqwest::Client, url: &str, target_dir: &str, overwrite: OverwritePolicy, paths: &PathStyle, progress: bool, resume: bool) -> Result<DownloadOutcome, MyError> {
+    if target_dir == STDOUT_TARGET {
+        return download_to_stdout(client, url, progress).await;
+    }
+    let dest = Path::new(target_dir).join(url_filename(url)?);
+    download_to(client, url, &dest, overwrite, paths, progress, resume).await
+}
+
+/// Like download_file, but saves the artifact as `dest` instead of under its URL's filename.
+pub async fn download_to(client: &reqwest::Client, url: &str, dest: &Path, overwrite: OverwritePolicy, paths: &PathStyle, progress: bool, resume: bool) -> Result<DownloadOutcome, MyError> {
+    // 1. Ensure the target directory exists
+    if let Some(dir) = dest.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    // 2. The name the artifact is saved (and reported) under
+    let filename = dest.file_name().map(|name| name.to_string_lossy().into_owned()).ok_or_else(|| {
+        MyError::LogicError(format!("Invalid download destination: {}", dest.display()))
+    })?;
+    let existing_path = dest.to_path_buf();
+
+    // Offline, an existing file is used as-is whatever the overwrite policy; a missing one can't be fetched
+    if settings().offline {
+        if existing_path.is_file() {
+            let bytes = tokio::fs::metadata(&existing_path).await?.len();
+            let path = existing_path.display().to_string();
+            emit(&Event::DownloadComplete { file: &filename, path: Some(&path), bytes, skipped: false, from_cache: true });
+            return Ok(DownloadOutcome { path: existing_path, bytes, skipped: false, from_cache: true });
+        }
+        return Err(MyError::LogicError(format!(
+            "Offline mode: {} is not present in the download directory.",
+            paths.render(&existing_path)
+        )));
+    }
+
+    // Resolve collisions with an already existing file according to the overwrite policy
+    let dest_path = match resolve_collision(&existing_path, overwrite)? {
+        Some(path) => path,
+        None => {
+            let bytes = tokio::fs::metadata(&existing_path).await?.len();
+            let path = existing_path.display().to_string();
+            emit(&Event::DownloadComplete { file: &filename, path: Some(&path), bytes, skipped: true, from_cache: false });
+            return Ok(DownloadOutcome { path: existing_path, bytes, skipped: true, from_cache: false });
+        }
+    };
+
+    // The body is written to a ".part" file that is only renamed once complete, so an
+    // interrupted download never looks finished and can be resumed on the next run
+    let part_path = part_path_for(&dest_path);
+    // A file about to be overwritten is only fetched again if it changed on the server
+    let validators = if dest_path == existing_path { read_source_meta(&dest_path).await } else { None };
+    status!("\nDownloading {} to {}...", filename, paths.render(&dest_path));
+
+    // 3. Try each mirror in turn, then the original URL
+    let sources = download_sources(url)?;
+    // If this future is dropped mid-download (e.g. cancelled by a timeout), drop the partial file
+    let mut cleanup = PartCleanup::new(&part_path);
+    let mut bytes = 0;
+    let mut meta = None;
+    for (source, original) in &sources {
+        let conditional = validators.as_ref().filter(|m| m.url == *source);
+        match fetch_part(client, source, &part_path, *original, &filename, progress, resume, conditional).await {
+            Ok(None) => {
+                cleanup.armed = false;
+                status!("{} is unchanged on the server (304 Not Modified), keeping it.", paths.render(&dest_path));
+                let bytes = tokio::fs::metadata(&dest_path).await?.len();
+                let path = dest_path.display().to_string();
+                emit(&Event::DownloadComplete { file: &filename, path: Some(&path), bytes, skipped: true, from_cache: false });
+                return Ok(DownloadOutcome { path: dest_path, bytes, skipped: true, from_cache: false });
+            }
+            Ok(Some((total, validators))) => {
+                if *original && sources.len() > 1 {
+                    status!("Downloaded from the original URL: {}", source);
+                } else if !*original {
+                    status!("Downloaded from mirror: {}", source);
+                }
+                bytes = total;
+                meta = Some(validators);
+                break;
+            }
+            Err(e) if !*original => warn!("Warning: mirror {} failed ({}), trying the next source.", source, e),
+            Err(e) => {
+                // A failed download keeps its partial file so the next run can resume it
+                cleanup.armed = false;
+                return Err(e);
+            }
+        }
+    }
+    cleanup.armed = false;
+
+    // 4. Move the completed file into place
+    if dest_path.exists() {
+        tokio::fs::remove_file(&dest_path).await?;
+    }
+    if tokio::fs::rename(&part_path, &dest_path).await.is_err() {
+        // A --temp-dir on another filesystem can't be renamed across
+        tokio::fs::copy(&part_path, &dest_path).await?;
+        tokio::fs::remove_file(&part_path).await?;
+    }
+    if let Some(meta) = meta {
+        write_source_meta(&dest_path, &meta).await;
+    }
+    let path = dest_path.display().to_string();
+    emit(&Event::DownloadComplete { file: &filename, path: Some(&path), bytes, skipped: false, from_cache: false });
+
+    Ok(DownloadOutcome { path: dest_path, bytes, skipped: false, from_cache: false })
+}
+
+// Tracks a `.part` file as in flight while alive, and removes it when dropped while armed, i.e.
+// when its download was cancelled
+struct PartCleanup<'a> {
+    path: &'a Path,
+    armed: bool,
+}
+
+impl<'a> PartCleanup<'a> {
+    fn new(path: &'a Path) -> Self {
+        IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner()).push(path.to_path_buf());
+        PartCleanup { path, armed: true }
+    }
+}
+
+impl Drop for PartCleanup<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(i) = in_flight.iter().position(|path| path == self.path) {
+            in_flight.swap_remove(i);
+        }
+        if self.armed {
+            let _ = std::fs::remove_file(self.path);
+        }
+    }
+}
+
+// Helper: download `url` into the `.part` file, resuming from its current end when `resume` is
+// set. With `validators` from an earlier download of the same URL, the request is conditional.
+// Returns the size of the completed file and its validators, or None if the server answered
+// 304 Not Modified (and nothing was written).
+#[allow(clippy::too_many_arguments)]
+async fn fetch_part(
+    client: &reqwest::Client,
+    url: &str,
+    part_path: &Path,
+    authorized: bool,
+    filename: &str,
+    progress: bool,
+    resume: bool,
+    validators: Option<&SourceMeta>,
+) -> Result<Option<(u64, SourceMeta)>, MyError> {
+    let offset = if resume {
+        tokio::fs::metadata(part_path).await.map(|m| m.len()).unwrap_or(0)
+    } else {
+        0
+    };
+    if offset > 0 {
+        status!("Resuming {} at {}...", filename, format_bytes(offset));
+    }
+    let get = || if authorized { with_auth(client.get(url)) } else { client.get(url) };
+
+    // Perform the request, asking only for the missing bytes when resuming, or only for a
+    // changed file when the existing one's validators are known
+    let mut response = send_with_retry(|| {
+        let request = get();
+        if offset > 0 {
+            request.header(reqwest::header::RANGE, format!("bytes={}-", offset))
+        } else if let Some(meta) = validators {
+            let request = match &meta.etag {
+                Some(etag) => request.header(reqwest::header::IF_NONE_MATCH, etag),
+                None => request,
+            };
+            match &meta.last_modified {
+                Some(date) => request.header(reqwest::header::IF_MODIFIED_SINCE, date),
+                None => request,
+            }
+        } else {
+            request
+        }
+    })
+    .await?;
+
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    // The partial file can't be extended (e.g. it's larger than the artifact): start over
+    if offset > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        status!("Server rejected the resume range, restarting download.");
+        response = send_with_retry(get).await?;
+    }
+
+    let response = ensure_success(response)?;
+    let header = |name: reqwest::header::HeaderName| {
+        response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+    };
+    let mut meta = SourceMeta {
+        url: url.to_string(),
+        etag: header(reqwest::header::ETAG),
+        last_modified: header(reqwest::header::LAST_MODIFIED),
+        size: 0,
+    };
+
+    // Open the partial file and stream the body into it chunk by chunk. Only a
+    // 206 Partial Content response continues the existing bytes; a 200 resends everything.
+    let appending = offset > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    if offset > 0 && !appending {
+        status!("Server does not support resuming, restarting download.");
+    }
+    let mut dest_file = if appending {
+        tokio::fs::OpenOptions::new().append(true).open(part_path).await?
+    } else {
+        File::create(part_path).await?
+    };
+
+    let start = if appending { offset } else { 0 };
+    let declared = response.content_length();
+    let pb = download_progress(declared.map(|len| start + len), progress, filename);
+    pb.set_position(start);
+    let mut events = ProgressEvents::new(filename, declared.map(|len| start + len));
+    let mut written: u64 = 0;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        dest_file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        pb.inc(chunk.len() as u64);
+        events.update(start + written);
+        throttle(chunk.len()).await;
+    }
+    dest_file.flush().await?;
+    drop(dest_file);
+    pb.finish_and_clear();
+
+    // A connection closed early can still end the body stream cleanly; don't keep a truncated file
+    if let Err(e) = check_length(url, declared, written) {
+        let _ = tokio::fs::remove_file(part_path).await;
+        return Err(e);
+    }
+    meta.size = start + written;
+    Ok(Some((start + written, meta)))
+}
+
+// Helper: stream an artifact to stdout, trying the mirrors first like download_to. Bytes written
+// to stdout can't be taken back, so a source is only skipped when it fails before its body starts.
+// The progress bar draws on stderr, like all other output should in this mode (see set_json_output).
+async fn download_to_stdout(client: &reqwest::Client, url: &str, progress: bool) -> Result<DownloadOutcome, MyError> {
+    let filename = url_filename(url)?;
+    if settings().offline {
+        return Err(MyError::LogicError(format!("Offline mode: {} can't be streamed to stdout.", filename)));
+    }
+    status!("\nDownloading {} to stdout...", filename);
+
+    let mut response = None;
+    for mirror in mirror_urls(url)? {
+        match send_with_retry(|| client.get(&mirror)).await.and_then(ensure_success) {
+            Ok(mirrored) => {
+                status!("Downloading from mirror: {}", mirror);
+                response = Some(mirrored);
+                break;
+            }
+            Err(e) => warn!("Warning: mirror {} failed ({}), trying the next source.", mirror, e),
+        }
+    }
+    let response = match response {
+        Some(response) => response,
+        None => ensure_success(send_with_retry(|| with_auth(client.get(url))).await?)?,
+    };
+
+    let declared = response.content_length();
+    let pb = download_progress(declared, progress, &filename);
+    let mut events = ProgressEvents::new(&filename, declared);
+    let mut stdout = tokio::io::stdout();
+    let mut written: u64 = 0;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        stdout.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        pb.inc(chunk.len() as u64);
+        events.update(written);
+        throttle(chunk.len()).await;
+    }
+    stdout.flush().await?;
+    pb.finish_and_clear();
+    check_length(url, declared, written)?;
+
+    emit(&Event::DownloadComplete { file: &filename, path: None, bytes: written, skipped: false, from_cache: false });
+    Ok(DownloadOutcome { path: PathBuf::from(STDOUT_TARGET), bytes: written, skipped: false, from_cache: false })
+}
+
+// Helper: fail if a response body's length differs from its declared Content-Length
+fn check_length(url: &str, declared: Option<u64>, received: u64) -> Result<(), MyError> {
+    match declared {
+        Some(expected) if expected != received => Err(MyError::LogicError(format!(
+            "Incomplete download of {}: expected {} bytes, received {}.",
+            url, expected, received
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// Validators of a downloaded file, kept next to it (see source_meta_path) so the next download
+// over it can be a conditional request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SourceMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    // Size of the file the validators describe, to notice it was replaced since
+    size: u64,
+}
+
+/// Path of the sidecar holding a downloaded file's ETag/Last-Modified, e.g. "terraform.zip.meta".
+/// Remove it along with the file.
+pub fn source_meta_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".meta");
+    path.with_file_name(name)
+}
+
+// Helper: the validators of an existing download, if recorded and still matching its size
+async fn read_source_meta(path: &Path) -> Option<SourceMeta> {
+    let text = tokio::fs::read_to_string(source_meta_path(path)).await.ok()?;
+    let meta: SourceMeta = serde_json::from_str(&text).ok()?;
+    let size = tokio::fs::metadata(path).await.ok()?.len();
+    (meta.size == size).then_some(meta)
+}
+
+// Helper: record a finished download's validators, or drop stale ones when the server sent none.
+// Failing to write them only costs a full download next time.
+async fn write_source_meta(path: &Path, meta: &SourceMeta) {
+    let meta_path = source_meta_path(path);
+    if meta.etag.is_none() && meta.last_modified.is_none() {
+        let _ = tokio::fs::remove_file(&meta_path).await;
+        return;
+    }
+    match serde_json::to_string(meta) {
+        Ok(json) => {
+            if let Err(e) = tokio::fs::write(&meta_path, json).await {
+                debug!("Could not write {}: {}", meta_path.display(), e);
+            }
+        }
+        Err(e) => debug!("Could not serialize download metadata: {}", e),
+    }
+}
+
+// Helper: path of the in-progress download for a destination file
+fn part_path_for(dest_path: &Path) -> PathBuf {
+    let mut name = dest_path.file_name().unwrap_or_default().to_os_string();
+    match &settings().temp_dir {
+        // Tagged with a hash of the destination so same-named files from different directories
+        // don't share a partial file, while a rerun still finds its own to resume
+        Some(dir) => {
+            let digest = Sha256::digest(dest_path.to_string_lossy().as_bytes());
+            name.push(format!("-{:.8}.part", format!("{:x}", digest)));
+            dir.join(name)
+        }
+        None => {
+            name.push(".part");
+            dest_path.with_file_name(name)
+        }
+    }
+}
+
+/// Query an artifact's size in bytes with a HEAD request, falling back to a one-byte ranged GET
+/// for servers that reject HEAD or leave the size out (None if neither reports it)
+pub async fn get_content_length(client: &reqwest::Client, url: &str) -> Result<Option<u64>, MyError> {
+    let header = |response: &reqwest::Response, name| {
+        response.headers().get(name).and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok()).map(str::to_string)
+    };
+    match send_with_retry(|| with_auth(client.head(url))).await.and_then(ensure_success) {
+        Ok(response) => {
+            if let Some(size) = header(&response, reqwest::header::CONTENT_LENGTH).and_then(|v| v.parse::<u64>().ok()) {
+                return Ok(Some(size));
+            }
+        }
+        Err(e) => debug!("HEAD {} failed ({}), trying a ranged GET", url, e),
+    }
+
+    let response = ensure_success(
+        send_with_retry(|| with_auth(client.get(url).header(reqwest::header::RANGE, "bytes=0-0"))).await?,
+    )?;
+    // A partial response gives the full size after the slash of "bytes 0-0/<size>"; a server that
+    // ignores the range sends the whole file, whose length is the size (the body is never read)
+    let size = if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+        header(&response, reqwest::header::CONTENT_RANGE).and_then(|v| v.rsplit('/').next()?.trim().parse::<u64>().ok())
+    } else {
+        header(&response, reqwest::header::CONTENT_LENGTH).and_then(|v| v.parse::<u64>().ok())
+    };
+    Ok(size)
+}
+
+/// Format a byte count for humans (e.g. "25.3 MiB")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// --- Checksum Verification ---
+
+/// Fetch the release's SHA256SUMS file and return the expected hash for `filename`.
+/// With a signing key, the SHA256SUMS signature is verified before the hash is trusted.
+pub async fn fetch_expected_checksum(client: &reqwest::Client, release: &Release, filename: &str, signing_key: Option<&SignedPublicKey>) -> Result<String, MyError> {
+    let sums_url = release.url_shasums.as_deref().ok_or_else(|| {
+        MyError::LogicError(format!("Release {} does not publish a SHA256SUMS file.", release.version))
+    })?;
+
+    let sums = fetch_bytes(client, sums_url).await?;
+
+    if let Some(key) = signing_key {
+        verify_release_signature(client, release, sums_url, &sums, key).await?;
+        status!("SHA256SUMS signature verified.");
+    }
+
+    let sums = String::from_utf8_lossy(&sums);
+    parse_checksum(&sums, filename).ok_or_else(|| {
+        MyError::LogicError(format!("No checksum for {} found in {}", filename, sums_url))
+    })
+}
+
+// Helper: GET a small resource fully into memory
+async fn fetch_bytes(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, MyError> {
+    let response = send_with_retry(|| with_auth(client.get(url))).await?;
+    read_bytes(response).await
+}
+
+// Helper: read a response body fully into memory, failing on an error status
+async fn read_bytes(response: reqwest::Response) -> Result<Vec<u8>, MyError> {
+    Ok(ensure_success(response)?.bytes().await?.to_vec())
+}
+
+// --- Signature Verification ---
+
+/// Load the public key used to verify SHA256SUMS signatures: either a user-supplied armored key,
+/// or HashiCorp's published key, which must match the pinned fingerprint
+pub async fn load_signing_key(client: &reqwest::Client, key_path: Option<&str>) -> Result<SignedPublicKey, MyError> {
+    let (armored, expected_fingerprint) = match key_path {
+        Some(path) => (tokio::fs::read_to_string(path).await?, None),
+        None => {
+            // Fetched without the auth token, which is only meant for the releases endpoints
+            let response = send_with_retry(|| client.get(HASHICORP_GPG_KEY_URL)).await?;
+            let bytes = read_bytes(response).await?;
+            (String::from_utf8_lossy(&bytes).into_owned(), Some(HASHICORP_GPG_FINGERPRINT))
+        }
+    };
+
+    let (key, _) = SignedPublicKey::from_string(&armored)
+        .map_err(|e| MyError::LogicError(format!("Invalid GPG public key: {}", e)))?;
+    key.verify_bindings()
+        .map_err(|e| MyError::LogicError(format!("Invalid GPG public key: {}", e)))?;
+
+    if let Some(expected) = expected_fingerprint {
+        let actual = format!("{:X}", key.fingerprint());
+        if actual != expected {
+            return Err(MyError::LogicError(format!(
+                "Unexpected fingerprint for HashiCorp's public key: expected {}, got {}",
+                expected, actual
+            )));
+        }
+    }
+
+    Ok(key)
+}
+
+// Download the detached signature(s) of SHA256SUMS and check that one verifies against the key
+async fn verify_release_signature(client: &reqwest::Client, release: &Release, sums_url: &str, sums: &[u8], key: &SignedPublicKey) -> Result<(), MyError> {
+    let mut candidates = release.url_shasums_signatures.clone();
+    let default_sig = format!("{}.sig", sums_url);
+    if !candidates.contains(&default_sig) {
+        candidates.push(default_sig);
+    }
+
+    let mut last_err = None;
+    for sig_url in &candidates {
+        let result = match fetch_bytes(client, sig_url).await {
+            Ok(signature) => verify_signature(sums, &signature, key),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| MyError::LogicError("No SHA256SUMS signature available.".to_string())))
+}
+
+/// Verify a detached (binary or armored) signature over `data` with the key or any of its subkeys
+pub fn verify_signature(data: &[u8], signature: &[u8], key: &SignedPublicKey) -> Result<(), MyError> {
+    let (sig, _) = DetachedSignature::from_reader_single(signature)
+        .map_err(|e| MyError::LogicError(format!("Invalid GPG signature: {}", e)))?;
+
+    if sig.verify(&key.primary_key, data).is_ok()
+        || key.public_subkeys.iter().any(|sub| sig.verify(sub, data).is_ok())
+    {
+        return Ok(());
+    }
+    Err(MyError::LogicError(format!(
+        "GPG signature verification of SHA256SUMS failed for key {:X}",
+        key.fingerprint()
+    )))
+}
+
+/// Find the hash for `filename` in SHA256SUMS-formatted text (`<hex>  <filename>` per line)
+pub fn parse_checksum(sums: &str, filename: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        // A leading '*' marks binary mode in the sha256sum format
+        let name = name.trim_start().trim_start_matches('*');
+        (name == filename).then(|| hash.to_lowercase())
+    })
+}
+
+/// Compute the SHA256 of a file as lowercase hex
+pub async fn sha256_file(path: &Path) -> Result<String, MyError> {
+    let path = path.to_path_buf();
+    task::spawn_blocking(move || -> Result<String, MyError> {
+        let mut file = StdFile::open(&path)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))?
+}
+
+/// Verify a file's SHA256 against the expected hex digest
+pub async fn verify_checksum(path: &Path, expected_hex: &str) -> Result<(), MyError> {
+    let actual = sha256_file(path).await?;
+    if !actual.eq_ignore_ascii_case(expected_hex) {
+        return Err(MyError::LogicError(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            path.display(), expected_hex, actual
+        )));
+    }
+    Ok(())
+}
+
+// Helper: check for .exe extension
+fn has_exe_ext(p: &Path) -> bool {
+    p.extension().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("exe")).unwrap_or(false)
+}
+
+// Helper: recognize native executable formats by their leading magic bytes (ELF, Mach-O incl.
+// universal binaries, PE). Unix binaries carry no extension, so the content has to tell.
+fn has_executable_magic(header: &[u8]) -> bool {
+    header.starts_with(b"\x7fELF")
+        || header.starts_with(b"MZ")
+        || matches!(
+            header.get(..4),
+            Some([0xfe, 0xed, 0xfa, 0xce | 0xcf] | [0xce | 0xcf, 0xfa, 0xed, 0xfe] | [0xca, 0xfe, 0xba, 0xbe])
+        )
+}
+
+// Helper: decide whether an archive member is an executable worth keeping. Windows builds are
+// recognized by their .exe extension; Linux/macOS/BSD builds by their executable bit or magic bytes.
+fn is_executable_entry(name: &Path, unix_mode: Option<u32>, header: &[u8]) -> bool {
+    has_exe_ext(name)
+        || unix_mode.map(|mode| mode & 0o111 != 0).unwrap_or(false)
+        || has_executable_magic(header)
+}
+
+// Process-wide file name filters for extraction, set from --extract-only
+static EXTRACT_ONLY: OnceLock<Vec<Regex>> = OnceLock::new();
+
+/// Extract only the executables whose file name matches one of these globs (`*` matches any run
+/// of characters, `?` a single one), e.g. "consul" or "vault-*". Without patterns, or a call,
+/// every executable is extracted. Applies process-wide, to every extraction function; only the
+/// first call takes effect.
+pub fn set_extract_only(patterns: &[String]) -> Result<(), MyError> {
+    let filters = patterns.iter().map(|pattern| product_filter(pattern, false)).collect::<Result<Vec<_>, _>>()?;
+    let _ = EXTRACT_ONLY.set(filters);
+    Ok(())
+}
+
+// Helper: whether an archive member passes the --extract-only filters, judging by its file name
+fn wanted_entry(name: &Path) -> bool {
+    match EXTRACT_ONLY.get() {
+        Some(filters) if !filters.is_empty() => {
+            let file_name = name.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+            filters.iter().any(|filter| filter.is_match(&file_name))
+        }
+        _ => true,
+    }
+}
+
+// Helper: read up to the first 4 bytes of a stream for magic detection, returns the filled length
+fn read_header<R: std::io::Read>(reader: &mut R, header: &mut [u8; 4]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Check whether a file on disk is an executable (see is_executable_entry)
+pub fn is_executable_file(path: &Path) -> bool {
+    #[cfg(unix)]
+    let unix_mode = {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).ok().map(|m| m.permissions().mode())
+    };
+    #[cfg(not(unix))]
+    let unix_mode = None;
+
+    let mut header = [0u8; 4];
+    let len = StdFile::open(path).and_then(|mut f| read_header(&mut f, &mut header)).unwrap_or(0);
+    is_executable_entry(path, unix_mode, &header[..len])
+}
+
+/// The architectures a native executable was built for, in the releases API's spelling (e.g.
+/// "amd64"), read from its ELF, Mach-O or PE header. Universal Mach-O binaries list each one they
+/// contain. Empty if the file isn't a recognized executable or its machine type is unknown.
+pub fn detect_binary_arch(path: &Path) -> Vec<&'static str> {
+    use std::io::Read;
+    // Enough for every header consulted below, including a PE header after a long DOS stub
+    let mut header = Vec::new();
+    if StdFile::open(path).and_then(|f| f.take(64 * 1024).read_to_end(&mut header)).is_err() {
+        return Vec::new();
+    }
+    let u16_at = |offset: usize, big_endian: bool| {
+        let bytes: [u8; 2] = header.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize, big_endian: bool| {
+        let bytes: [u8; 4] = header.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    let mach_arch = |cputype: u32| match cputype {
+        7 => Some("386"),
+        0x0100_0007 => Some("amd64"),
+        12 => Some("arm"),
+        0x0100_000c => Some("arm64"),
+        _ => None,
+    };
+
+    match header.get(..4) {
+        // ELF: e_machine at offset 18, in the byte order given by EI_DATA
+        Some(b"\x7fELF") => {
+            let big_endian = header.get(5) == Some(&2);
+            let arch = match u16_at(18, big_endian) {
+                Some(3) => "386",
+                Some(62) => "amd64",
+                Some(40) => "arm",
+                Some(183) => "arm64",
+                Some(21) if !big_endian => "ppc64le",
+                Some(22) => "s390x",
+                _ => return Vec::new(),
+            };
+            vec![arch]
+        }
+        // Mach-O: cputype right after the magic, whose byte order tells the file's
+        Some([0xfe, 0xed, 0xfa, 0xce | 0xcf]) => u32_at(4, true).and_then(mach_arch).into_iter().collect(),
+        Some([0xce | 0xcf, 0xfa, 0xed, 0xfe]) => u32_at(4, false).and_then(mach_arch).into_iter().collect(),
+        // Universal Mach-O: a big-endian count of 20-byte entries, each starting with a cputype
+        Some([0xca, 0xfe, 0xba, 0xbe]) => {
+            let count = u32_at(4, true).unwrap_or(0) as usize;
+            (0..count.min(16)).filter_map(|i| u32_at(8 + i * 20, true).and_then(mach_arch)).collect()
+        }
+        // PE: the DOS header points at the "PE\0\0" signature, followed by the machine field
+        Some([b'M', b'Z', ..]) => {
+            let Some(pe) = u32_at(0x3c, false).map(|offset| offset as usize) else { return Vec::new() };
+            if header.get(pe..pe + 4) != Some(b"PE\0\0") {
+                return Vec::new();
+            }
+            let arch = match u16_at(pe + 4, false) {
+                Some(0x14c) => "386",
+                Some(0x8664) => "amd64",
+                Some(0x1c0 | 0x1c4) => "arm",
+                Some(0xaa64) => "arm64",
+                _ => return Vec::new(),
+            };
+            vec![arch]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Helper: mark an extracted binary as executable (0755) on Unix, where modes are lost by the internal extractor
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+// Process-wide switch for keeping archive modes, set from --preserve-mode
+static PRESERVE_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Give extracted executables the Unix mode stored in the archive instead of 0755, where the
+/// archive has an executable one. Setuid, setgid and sticky bits are never applied. Applies
+/// process-wide, to every extraction function.
+pub fn set_preserve_mode(enabled: bool) {
+    PRESERVE_MODE.store(enabled, Ordering::Relaxed);
+}
+
+// Helper: set an extracted binary's permissions: 0755, or its `stored` archive mode when
+// preserving modes. A mode without execute bits comes from an archive made on a system without
+// Unix modes (the extractors derive one from DOS attributes) and is ignored.
+#[cfg(unix)]
+fn set_extracted_mode(path: &Path, stored: Option<u32>) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    match stored.filter(|mode| PRESERVE_MODE.load(Ordering::Relaxed) && mode & 0o111 != 0) {
+        Some(mode) => std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o777)),
+        None => set_executable(path),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_extracted_mode(_path: &Path, _stored: Option<u32>) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Check for .zip extension
+pub fn has_zip_ext(p: &Path) -> bool {
+    p.extension().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("zip")).unwrap_or(false)
+}
+
+/// Check for .tar.gz / .tgz extension
+pub fn has_targz_ext(p: &Path) -> bool {
+    let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+/// Check for a .gz extension on a single compressed file, i.e. not a .tar.gz
+pub fn has_gz_ext(p: &Path) -> bool {
+    let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    name.ends_with(".gz") && !name.ends_with(".tar.gz")
+}
+
+/// Check for an archive format that extract_archive supports
+pub fn is_archive(p: &Path) -> bool {
+    has_zip_ext(p) || has_targz_ext(p) || has_gz_ext(p)
+}
+
+/// Check for .msi extension
+pub fn has_msi_ext(p: &Path) -> bool {
+    p.extension().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("msi")).unwrap_or(false)
+}
+
+// Helper: resolve the path to write to when `dest` may already exist, according to the policy.
+// Returns None when the existing file should be kept.
+fn resolve_collision(dest: &Path, policy: OverwritePolicy) -> std::io::Result<Option<PathBuf>> {
+    if !dest.exists() {
+        return Ok(Some(dest.to_path_buf()));
+    }
+    match policy {
+        OverwritePolicy::Skip => Ok(None),
+        OverwritePolicy::Overwrite => Ok(Some(dest.to_path_buf())),
+        OverwritePolicy::Rename => {
+            // Add a numeric suffix before the extension until the name is free
+            let parent = dest.parent().unwrap_or_else(|| Path::new(""));
+            let stem = dest.file_stem().and_then(|s| s.to_str()).unwrap_or("program");
+            let ext = dest.extension().and_then(|s| s.to_str());
+            let mut idx = 1u32;
+            loop {
+                let name = match ext {
+                    Some(ext) => format!("{}-{}.{}", stem, idx, ext),
+                    None => format!("{}-{}", stem, idx),
+                };
+                let candidate = parent.join(name);
+                if !candidate.exists() {
+                    return Ok(Some(candidate));
+                }
+                idx += 1;
+            }
+        }
+        OverwritePolicy::Error => Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("File already exists: {}", dest.display()),
+        )),
+    }
+}
+
+// Helper: move a file, replacing any existing destination
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if to.exists() {
+        // Remove destination first to allow rename on Windows
+        let _ = std::fs::remove_file(to);
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(_) => {
+            // If rename fails (e.g., across filesystems), copy and remove
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }
+    }
+}
+
+// Helper: recursively move executable files from src to dest root (flatten), returns count.
+// Symlinks are never followed, and every file moved must resolve to a path inside src, so a
+// crafted archive can't make this pick up (or overwrite) files elsewhere.
+fn move_exes_recursively(src: &Path, dest_root: &Path, overwrite: OverwritePolicy) -> std::io::Result<usize> {
+    let src_root = std::fs::canonicalize(src)?;
+    let mut count = 0usize;
+    let mut stack = vec![src.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                warn!("Warning: skipping symlink {} from the archive.", path.display());
+            } else if file_type.is_dir() {
+                stack.push(path);
+            } else if file_type.is_file() && wanted_entry(&path) && is_executable_file(&path) {
+                if !std::fs::canonicalize(&path)?.starts_with(&src_root) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("{} escapes the extraction directory", path.display()),
+                    ));
+                }
+                let file_name = path.file_name().unwrap();
+                // The system tools already applied the archive's mode to the extracted file
+                #[cfg(unix)]
+                let stored = {
+                    use std::os::unix::fs::PermissionsExt;
+                    Some(std::fs::metadata(&path)?.permissions().mode())
+                };
+                #[cfg(not(unix))]
+                let stored = None;
+                // Resolve collisions with existing files according to the overwrite policy
+                if let Some(dest_path) = resolve_collision(&dest_root.join(file_name), overwrite)? {
+                    move_file(&path, &dest_path)?;
+                    set_extracted_mode(&dest_path, stored)?;
+                    count += 1;
+                }
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Install an executable into `dir` (created if needed), replacing a previous copy, and return
+/// its new path. On Unix the file is moved there and marked executable; on Windows it's copied,
+/// leaving the extracted file in place.
+pub fn install_executable(exe: &Path, dir: &Path) -> Result<PathBuf, MyError> {
+    let name = exe
+        .file_name()
+        .ok_or_else(|| MyError::LogicError(format!("Invalid executable path: {}", exe.display())))?;
+    std::fs::create_dir_all(dir)?;
+    let dest = dir.join(name);
+    if cfg!(windows) {
+        std::fs::copy(exe, &dest)?;
+    } else {
+        move_file(exe, &dest)?;
+    }
+    set_executable(&dest)?;
+    Ok(dest)
+}
+
+/// Whether `dir` is one of the directories on the PATH environment variable
+pub fn dir_on_path(dir: &Path) -> bool {
+    let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let dir = canonical(dir);
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|entry| canonical(&entry) == dir))
+}
+
+/// Extract only executable files using OS facilities on Windows (PowerShell Expand-Archive),
+/// falling back to zip crate on other platforms. Returns number of executable files extracted.
+pub async fn extract_exe_from_zip(zip_path: &Path, dest_dir: &Path, overwrite: OverwritePolicy) -> Result<usize, MyError> {
+    // External tools extract every entry as-is, so an archive with entries that would land outside
+    // the extraction directory (zip-slip) or symlinks is left to the internal extractor, which skips them
+    if let Some(entry) = unsafe_zip_entry(zip_path).await? {
+        warn!("Warning: {} contains an unsafe entry ({}); extracting it internally.", zip_path.display(), entry);
+        return extract_zip_internally(zip_path, dest_dir, overwrite).await;
+    }
+
+    #[cfg(windows)]
+    {
+        // Create a temporary extraction directory, removed again however this block is left
+        let scratch = scratch_dir("hcd-extract").await?;
+        let tmp_dir = scratch.0.clone();
+
+        // Use PowerShell's Expand-Archive to extract contents
+    let status = TokioCommand::new("powershell")
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                &format!(
+                    "Expand-Archive -LiteralPath {} -DestinationPath {} -Force",
+                    format!("\"{}\"", zip_path.display()),
+                    format!("\"{}\"", tmp_dir.display())
+                ),
+            ])
+            .status()
+            .await
+            .map_err(|e| MyError::LogicError(format!("Failed to invoke PowerShell Expand-Archive: {}", e)))?;
+
+        // On failure, fall back to the internal extractor
+        if status.success() {
+            // Move only executable files from tmp_dir to dest_dir
+            let count = task::spawn_blocking({
+                let tmp_dir = tmp_dir.clone();
+                let dest_dir = dest_dir.to_path_buf();
+                move || move_exes_recursively(&tmp_dir, &dest_dir, overwrite)
+            })
+            .await
+            .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))
+            .and_then(|r| r.map_err(MyError::Io))?;
+
+            return Ok(count);
+        }
+    }
+
+    // On Unix/macOS: try system tools first, then fallback to internal
+    #[cfg(all(unix, not(windows)))]
+    {
+        // Create a temporary extraction directory, removed again however this block is left
+        let scratch = scratch_dir("hcd-extract").await?;
+        let tmp_dir = scratch.0.clone();
+
+        // Helper to run a command and return whether it succeeded. The tools' listings would
+        // corrupt JSON or events on stdout, so they're dropped then.
+        async fn run_status(mut cmd: TokioCommand) -> bool {
+            if json_output() {
+                cmd.stdout(std::process::Stdio::null());
+            }
+            matches!(cmd.status().await, Ok(s) if s.success())
+        }
+
+        // 1) Try unzip (widely available on macOS and many Linux distros)
+        let unzip_ok = run_status({
+            let mut c = TokioCommand::new("unzip");
+            c.arg("-o").arg(zip_path).arg("-d").arg(&tmp_dir);
+            c
+        }).await;
+
+        // 2) macOS specific alternative: ditto
+        #[cfg(target_os = "macos")]
+        let ditto_ok = if !unzip_ok {
+            run_status({
+                let mut c = TokioCommand::new("ditto");
+                c.args(["-x", "-k"]).arg(zip_path).arg(&tmp_dir);
+                c
+            }).await
+        } else { false };
+        #[cfg(not(target_os = "macos"))]
+        let ditto_ok = false;
+
+        // 3) Try bsdtar as another common option
+        let bsdtar_ok = if !unzip_ok && !ditto_ok {
+            run_status({
+                let mut c = TokioCommand::new("bsdtar");
+                c.args(["-xf"]).arg(zip_path).args(["-C"]).arg(&tmp_dir);
+                c
+            }).await
+        } else { false };
+
+    if unzip_ok || ditto_ok || bsdtar_ok {
+            // Move only executable files from tmp_dir to dest_dir
+            let count = task::spawn_blocking({
+                let tmp_dir = tmp_dir.clone();
+                let dest_dir = dest_dir.to_path_buf();
+        move || move_exes_recursively(&tmp_dir, &dest_dir, overwrite)
+            })
+            .await
+            .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))
+            .and_then(|r| r.map_err(MyError::Io))?;
+
+            return Ok(count);
+        }
+    }
+
+    // Fallback: internal ZIP parsing (keeps only executable entries) for all platforms
+    extract_zip_internally(zip_path, dest_dir, overwrite).await
+}
+
+// Helper: extract a ZIP's executables with the zip crate
+async fn extract_zip_internally(zip_path: &Path, dest_dir: &Path, overwrite: OverwritePolicy) -> Result<usize, MyError> {
+    let zip_path_buf = zip_path.to_path_buf();
+    let dest_dir_buf = dest_dir.to_path_buf();
+    task::spawn_blocking(move || -> Result<usize, MyError> {
+        let file = StdFile::open(&zip_path_buf)?;
+        extract_exes_from_archive(file, &dest_dir_buf, overwrite)
+    })
+    .await
+    .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))?
+}
+
+// Helper: whether a ZIP entry is a symlink, judging by its Unix mode
+fn is_zip_symlink(mode: Option<u32>) -> bool {
+    mode.is_some_and(|mode| mode & 0o170000 == 0o120000)
+}
+
+// Helper: the name of the first ZIP entry that escapes the extraction directory or is a symlink
+async fn unsafe_zip_entry(zip_path: &Path) -> Result<Option<String>, MyError> {
+    let zip_path_buf = zip_path.to_path_buf();
+    task::spawn_blocking(move || -> Result<Option<String>, MyError> {
+        let file = StdFile::open(&zip_path_buf)?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| MyError::LogicError(format!("Invalid ZIP file: {}", e)))?;
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(|e| MyError::LogicError(format!("ZIP read error: {}", e)))?;
+            if file.enclosed_name().is_none() || is_zip_symlink(file.unix_mode()) {
+                return Ok(Some(file.name().to_string()));
+            }
+        }
+        Ok(None)
+    })
+    .await
+    .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))?
+}
+
+/// Extract executable entries from any seekable ZIP source into dest_dir, returns count
+pub fn extract_exes_from_archive<R: std::io::Read + std::io::Seek>(reader: R, dest_dir: &Path, overwrite: OverwritePolicy) -> Result<usize, MyError> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| MyError::LogicError(format!("Invalid ZIP file: {}", e)))?;
+    let mut exe_count = 0usize;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| MyError::LogicError(format!("ZIP read error: {}", e)))?;
+        // Reject entries that would escape dest_dir (zip-slip), and symlinks, whose data is the link target
+        let enclosed = match file.enclosed_name() {
+            Some(p) if !is_zip_symlink(file.unix_mode()) => p.to_owned(),
+            _ => {
+                warn!("Warning: skipping unsafe ZIP entry '{}'.", file.name());
+                continue;
+            }
+        };
+        if file.is_dir() || !wanted_entry(&enclosed) {
+            continue;
+        }
+        let mode = file.unix_mode();
+        let mut header = [0u8; 4];
+        let header_len = read_header(&mut file, &mut header)?;
+        if is_executable_entry(&enclosed, mode, &header[..header_len])
+            && write_executable(&mut file, &header[..header_len], &enclosed, mode, dest_dir, overwrite)?
+        {
+            exe_count += 1;
+        }
+    }
+    Ok(exe_count)
+}
+
+/// Extract executable entries from a gzip-compressed tarball into dest_dir, returns count
+pub async fn extract_from_targz(archive_path: &Path, dest_dir: &Path, overwrite: OverwritePolicy) -> Result<usize, MyError> {
+    let archive_path = archive_path.to_path_buf();
+    let dest_dir = dest_dir.to_path_buf();
+    task::spawn_blocking(move || -> Result<usize, MyError> {
+        let file = StdFile::open(&archive_path)?;
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+        let mut exe_count = 0usize;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            // Links and directories can't be executables themselves
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry.path()?.into_owned();
+            // Only the file name is used, but an entry aiming outside dest_dir is suspect anyway
+            if name.components().any(|c| matches!(c, Component::ParentDir | Component::RootDir | Component::Prefix(_))) {
+                warn!("Warning: skipping unsafe tar entry '{}'.", name.display());
+                continue;
+            }
+            if !wanted_entry(&name) {
+                continue;
+            }
+            let mode = entry.header().mode().ok();
+            let mut header = [0u8; 4];
+            let header_len = read_header(&mut entry, &mut header)?;
+            if is_executable_entry(&name, mode, &header[..header_len])
+                && write_executable(&mut entry, &header[..header_len], &name, mode, &dest_dir, overwrite)?
+            {
+                exe_count += 1;
+            }
+        }
+        Ok(exe_count)
+    })
+    .await
+    .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))?
+}
+
+/// Decompress a gzipped single binary (e.g. terraform.gz from a mirror) into dest_dir under its
+/// name without ".gz", marked executable. Returns 1, or 0 when the overwrite policy keeps an
+/// existing file.
+pub async fn extract_gz_binary(gz_path: &Path, dest_dir: &Path, overwrite: OverwritePolicy) -> Result<usize, MyError> {
+    let gz_path = gz_path.to_path_buf();
+    let dest_dir = dest_dir.to_path_buf();
+    task::spawn_blocking(move || -> Result<usize, MyError> {
+        let name = gz_path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+        let binary = Path::new(&name[..name.len().saturating_sub(".gz".len())]);
+        if binary.as_os_str().is_empty() {
+            return Err(MyError::LogicError(format!("Cannot name the binary inside {}.", gz_path.display())));
+        }
+        if !wanted_entry(binary) {
+            return Ok(0);
+        }
+        let mut decoder = flate2::read::GzDecoder::new(StdFile::open(&gz_path)?);
+        let mut header = [0u8; 4];
+        let header_len = read_header(&mut decoder, &mut header)?;
+        if !has_executable_magic(&header[..header_len]) {
+            warn!("Warning: {} doesn't look like a native executable, decompressing it anyway.", gz_path.display());
+        }
+        Ok(usize::from(write_executable(&mut decoder, &header[..header_len], binary, None, &dest_dir, overwrite)?))
+    })
+    .await
+    .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))?
+}
+
+/// Extract only executable files from a .zip or .tar.gz archive into dest_dir, or decompress a
+/// gzipped single binary there, returns count
+pub async fn extract_archive(archive_path: &Path, dest_dir: &Path, overwrite: OverwritePolicy) -> Result<usize, MyError> {
+    tokio::fs::create_dir_all(dest_dir).await?;
+    let count = if has_targz_ext(archive_path) {
+        extract_from_targz(archive_path, dest_dir, overwrite).await?
+    } else if has_gz_ext(archive_path) {
+        extract_gz_binary(archive_path, dest_dir, overwrite).await?
+    } else if has_zip_ext(archive_path) {
+        extract_exe_from_zip(archive_path, dest_dir, overwrite).await?
+    } else {
+        return Err(MyError::LogicError(format!("Unsupported archive format: {}", archive_path.display())));
+    };
+    let file = archive_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    emit(&Event::ExtractComplete { file: &file, dir: &dest_dir.display().to_string(), files: count });
+    Ok(count)
+}
+
+// Helper: write an archive entry (whose first bytes were already read into `header`) into
+// dest_dir under its file name, flattening directories, with the entry's stored `mode` if modes
+// are preserved. Returns false when the overwrite policy keeps an existing file.
+fn write_executable<R: std::io::Read>(reader: &mut R, header: &[u8], entry_name: &Path, mode: Option<u32>, dest_dir: &Path, overwrite: OverwritePolicy) -> Result<bool, MyError> {
+    let filename = match entry_name.file_name() {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+    // Resolve destination path according to the overwrite policy
+    let outpath = match resolve_collision(&dest_dir.join(filename), overwrite)? {
+        Some(p) => p,
+        None => return Ok(false),
+    };
+    if outpath.exists() { let _ = std::fs::remove_file(&outpath); }
+    let mut outfile = std::fs::File::create(&outpath)?;
+    outfile.write_all(header)?;
+    std::io::copy(reader, &mut outfile)?;
+    drop(outfile);
+    set_extracted_mode(&outpath, mode)?;
+    Ok(true)
+}
+
+/// Re-extract executables from a kept archive that are missing from dest_dir or whose size doesn't
+/// match the archive entry (e.g. after an interrupted extraction). Returns number of repaired files.
+pub async fn repair_from_zip(zip_path: &Path, dest_dir: &Path) -> Result<usize, MyError> {
+    tokio::fs::create_dir_all(dest_dir).await?;
+    let zip_path_buf = zip_path.to_path_buf();
+    let dest_dir_buf = dest_dir.to_path_buf();
+    task::spawn_blocking(move || -> Result<usize, MyError> {
+        let file = StdFile::open(&zip_path_buf)?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| MyError::LogicError(format!("Invalid ZIP file: {}", e)))?;
+        let mut repaired = 0usize;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).map_err(|e| MyError::LogicError(format!("ZIP read error: {}", e)))?;
+            // Same guard as extraction: nothing outside dest_dir, and no symlinks, whose data is the link target
+            let enclosed = match file.enclosed_name() {
+                Some(p) if !is_zip_symlink(file.unix_mode()) => p.to_owned(),
+                _ => {
+                    warn!("Warning: skipping unsafe ZIP entry '{}'.", file.name());
+                    continue;
+                }
+            };
+            if file.is_dir() || !wanted_entry(&enclosed) {
+                continue;
+            }
+            let mut header = [0u8; 4];
+            let header_len = read_header(&mut file, &mut header)?;
+            if !is_executable_entry(&enclosed, file.unix_mode(), &header[..header_len]) {
+                continue;
+            }
+            let outpath = dest_dir_buf.join(enclosed.file_name().unwrap());
+            let intact = std::fs::metadata(&outpath).map(|m| m.is_file() && m.len() == file.size()).unwrap_or(false);
+            if intact {
+                continue;
+            }
+            // Write next to the destination first so a second interruption can't leave a truncated file behind
+            let mut tmp_name = outpath.file_name().unwrap().to_os_string();
+            tmp_name.push(".hcd-repair");
+            let tmp_path = outpath.with_file_name(tmp_name);
+            let mut outfile = std::fs::File::create(&tmp_path)?;
+            outfile.write_all(&header[..header_len])?;
+            std::io::copy(&mut file, &mut outfile)?;
+            drop(outfile);
+            if outpath.exists() { let _ = std::fs::remove_file(&outpath); }
+            std::fs::rename(&tmp_path, &outpath)?;
+            set_extracted_mode(&outpath, file.unix_mode())?;
+            status!("Repaired: {}", outpath.display());
+            repaired += 1;
+        }
+        Ok(repaired)
+    })
+    .await
+    .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))?
+}
+
+/// Largest archive [`download_and_extract_zip`] buffers in memory. Bigger ones, by their declared
+/// size or once the body outgrows it, go to a scratch file that is extracted like a regular
+/// download and removed afterwards.
+pub const STREAM_EXTRACT_MAX_MEMORY: u64 = 256 * 1024 * 1024;
+
+// Most memory reserved up front from a declared Content-Length, so a bogus header can't make the
+// process allocate a huge buffer before any byte arrives
+const MAX_PREALLOCATION: u64 = 64 * 1024 * 1024;
+
+/// Download a ZIP and extract its executables without writing the archive to disk. The ZIP
+/// central directory lives at the end of the archive, so entries can't be located until the
+/// whole body has arrived: the archive is buffered in memory instead of on disk, up to
+/// [`STREAM_EXTRACT_MAX_MEMORY`]. Mirrors are tried first, as for [`download_to`].
+pub async fn download_and_extract_zip(client: &reqwest::Client, url: &str, dest_dir: &Path, overwrite: OverwritePolicy, expected_sha256: Option<&str>, progress: bool) -> Result<usize, MyError> {
+    tokio::fs::create_dir_all(dest_dir).await?;
+    let file = url_filename(url)?;
+
+    status!("\nDownloading {} into memory for extraction...", url);
+    let sources = download_sources(url)?;
+    let mut fetched = None;
+    for (source, original) in &sources {
+        match fetch_spooled(client, source, *original, &file, progress, STREAM_EXTRACT_MAX_MEMORY).await {
+            Ok(spooled) => {
+                if *original && sources.len() > 1 {
+                    status!("Downloaded from the original URL: {}", source);
+                } else if !*original {
+                    status!("Downloaded from mirror: {}", source);
+                }
+                fetched = Some(spooled);
+                break;
+            }
+            Err(e) if !*original => warn!("Warning: mirror {} failed ({}), trying the next source.", source, e),
+            Err(e) => return Err(e),
+        }
+    }
+    let (spool, bytes, actual) = fetched.ok_or_else(|| MyError::LogicError(format!("No source left to download {}", url)))?;
+    emit(&Event::DownloadComplete { file: &file, path: None, bytes, skipped: false, from_cache: false });
+    status!("Download completed successfully ({}).", format_bytes(bytes));
+
+    if let Some(expected) = expected_sha256 {
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(MyError::LogicError(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                url, expected, actual
+            )));
+        }
+        status!("Checksum verified (SHA256: {}).", actual);
+    }
+
+    match spool {
+        Spool::Memory(bytes) => {
+            let dest_dir_buf = dest_dir.to_path_buf();
+            let count = task::spawn_blocking(move || extract_exes_from_archive(std::io::Cursor::new(bytes), &dest_dir_buf, overwrite))
+                .await
+                .map_err(|e| MyError::LogicError(format!("Task join error: {}", e)))??;
+            emit(&Event::ExtractComplete { file: &file, dir: &dest_dir.display().to_string(), files: count });
+            Ok(count)
+        }
+        Spool::File { scratch, path, file } => {
+            drop(file);
+            let count = extract_archive(&path, dest_dir, overwrite).await;
+            // Removes the archive along with its scratch directory
+            drop(scratch);
+            count
+        }
+    }
+}
+
+// Where download_and_extract_zip collects an archive: memory while it's small enough, a file in
+// a scratch directory once it isn't
+enum Spool {
+    Memory(Vec<u8>),
+    File { scratch: ScratchDir, path: PathBuf, file: File },
+}
+
+impl Spool {
+    async fn to_file(name: &str) -> Result<Spool, MyError> {
+        let scratch = scratch_dir("hcd-extract").await?;
+        let path = scratch.0.join(name);
+        let file = File::create(&path).await?;
+        Ok(Spool::File { scratch, path, file })
+    }
+
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), MyError> {
+        match self {
+            Spool::Memory(bytes) => bytes.extend_from_slice(chunk),
+            Spool::File { file, .. } => file.write_all(chunk).await?,
+        }
+        Ok(())
+    }
+}
+
+// Helper: download `url` for download_and_extract_zip, into memory or, beyond `max_memory`
+// bytes, into a scratch file. Only an `authorized` source gets the auth token. Returns where the
+// archive went, its size and its SHA256.
+async fn fetch_spooled(
+    client: &reqwest::Client,
+    url: &str,
+    authorized: bool,
+    file: &str,
+    progress: bool,
+    max_memory: u64,
+) -> Result<(Spool, u64, String), MyError> {
+    let response = ensure_success(send_with_retry(|| if authorized { with_auth(client.get(url)) } else { client.get(url) }).await?)?;
+
+    let declared = response.content_length();
+    let pb = download_progress(declared, progress, file);
+    let mut events = ProgressEvents::new(file, declared);
+    let mut spool = match declared {
+        Some(len) if len > max_memory => {
+            status!("{} is too large to buffer in memory ({}), saving it to a scratch file first.", file, format_bytes(len));
+            Spool::to_file(file).await?
+        }
+        _ => Spool::Memory(Vec::with_capacity(declared.unwrap_or(0).min(MAX_PREALLOCATION) as usize)),
+    };
+    let mut hasher = Sha256::new();
+    let mut received: u64 = 0;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        received += chunk.len() as u64;
+        if received > max_memory {
+            if let Spool::Memory(bytes) = &mut spool {
+                status!("{} outgrew the in-memory limit ({}), continuing in a scratch file.", file, format_bytes(max_memory));
+                let buffered = std::mem::take(bytes);
+                spool = Spool::to_file(file).await?;
+                spool.write(&buffered).await?;
+            }
+        }
+        spool.write(&chunk).await?;
+        hasher.update(&chunk);
+        pb.inc(chunk.len() as u64);
+        events.update(received);
+        throttle(chunk.len()).await;
+    }
+    pb.finish_and_clear();
+    check_length(url, declared, received)?;
+    if let Spool::File { file, .. } = &mut spool {
+        file.flush().await?;
+    }
+    Ok((spool, received, format!("{:x}", hasher.finalize())))
+}
+
+// --- Main Logic ---
+
+fn releases_url(base_url: &str, product: &str, license_class: &str) -> String {
+    format!("{}releases/{}?license_class={}", base_url, product, license_class)
+}
+
+/// Fetch all releases for a product, failing if there are none
+pub async fn fetch_releases(client: &reqwest::Client, product: &str, license_class: &str) -> Result<Vec<Release>, MyError> {
+    fetch_releases_from(client, api_url(), product, license_class).await
+}
+
+async fn fetch_releases_from(client: &reqwest::Client, base_url: &str, product: &str, license_class: &str) -> Result<Vec<Release>, MyError> {
+    let url = releases_url(base_url, product, license_class);
+    let key = format!("releases-{}-{}", license_class, product);
+    let all_releases: Vec<Release> = match get_json_cached(&url, &key, || with_auth(client.get(&url))).await {
+        Err(MyError::HttpStatus { status: 404, .. }) => {
+            return Err(MyError::LogicError(format!(
+                "Product '{}' not found (license class '{}').",
+                product, license_class
+            )));
+        }
+        result => result?,
+    };
+
+    if all_releases.is_empty() {
+        return Err(MyError::LogicError(format!(
+            "Product '{}' with license class '{}' not found or has no releases.",
+            product, license_class
+        )));
+    }
+
+    Ok(all_releases)
+}
+
+/// Check whether the latest supported stable release of a product ships a build for the platform
+pub async fn has_build_for(client: &reqwest::Client, product: &str, license_class: &str, target_os: &str, target_arch: &str) -> Result<bool, MyError> {
+    Downloader::builder()
+        .client(client.clone())
+        .license_class(license_class)
+        .platform(target_os, target_arch)
+        .build()?
+        .has_build(product)
+        .await
+}
+
+/// Compile a product name filter: a glob such as "vault*" matched against the whole name (`*`
+/// matches any run of characters, `?` a single one), or with `regex`, a regular expression
+/// matched anywhere in the name
+pub fn product_filter(pattern: &str, regex: bool) -> Result<Regex, MyError> {
+    let source = if regex {
+        pattern.to_string()
+    } else {
+        format!("^{}$", regex::escape(pattern).replace(r"\*", ".*").replace(r"\?", "."))
+    };
+    Regex::new(&source).map_err(|e| MyError::LogicError(format!("Invalid filter '{}': {}", pattern, e)))
+}
+
+/// Keep only the products that ship a build for the platform, checking up to `jobs` products at once
+pub async fn filter_products_with_build(
+    client: &reqwest::Client,
+    products: Vec<String>,
+    license_class: &str,
+    target_os: &str,
+    target_arch: &str,
+    jobs: usize,
+) -> Vec<String> {
+    match Downloader::builder()
+        .client(client.clone())
+        .license_class(license_class)
+        .platform(target_os, target_arch)
+        .build()
+    {
+        Ok(downloader) => downloader.products_with_build(products, jobs).await,
+        Err(e) => {
+            warn!("Warning: could not check builds: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Expand a product alias (built-in or user-defined via --alias) into the product name
+pub fn resolve_alias(product: &str, overrides: &[String]) -> Result<String, MyError> {
+    let resolved = resolve_alias_quiet(product, overrides)?;
+    if resolved != product {
+        status!("Expanding alias '{}' to '{}'", product, resolved);
+    }
+    Ok(resolved)
+}
+
+/// Same as resolve_alias, without reporting the expansion
+pub fn resolve_alias_quiet(product: &str, overrides: &[String]) -> Result<String, MyError> {
+    let mut aliases: HashMap<String, String> = PRODUCT_ALIASES
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    for entry in overrides {
+        let (alias, target) = entry.split_once('=').ok_or_else(|| {
+            MyError::LogicError(format!("Invalid alias '{}', expected ALIAS=PRODUCT.", entry))
+        })?;
+        let alias = alias.trim().to_lowercase();
+        let target = target.trim();
+        if target.is_empty() {
+            aliases.remove(&alias);
+        } else {
+            aliases.insert(alias, target.to_string());
+        }
+    }
+    Ok(aliases.get(&product.to_lowercase()).cloned().unwrap_or_else(|| product.to_string()))
+}
+
+// Helper: the host architecture as named in ARCH_MAPPING. Rust calls both endiannesses of 64-bit
+// POWER "powerpc64", but HashiCorp only builds the little-endian one.
+fn host_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "powerpc64" if cfg!(target_endian = "little") => "powerpc64le",
+        arch => arch,
+    }
+}
+
+// Helper: the distinct API values of a platform mapping, sorted, for error messages
+fn known_values(mapping: &HashMap<&'static str, &'static str>) -> String {
+    let mut values: Vec<&str> = mapping.values().copied().collect();
+    values.sort_unstable();
+    values.dedup();
+    values.join(", ")
+}
+
+/// Resolve OS and Arch if set to "auto"
+pub fn resolve_platform(os_arg: &str, arch_arg: &str) -> Result<(String, String), MyError> {
+    let os = if os_arg == "auto" {
+        OS_MAPPING.get(std::env::consts::OS).map(|s| s.to_string())
+            .ok_or_else(|| MyError::LogicError(format!(
+                "Unsupported operating system: {}. Pass one of {} with --os.",
+                std::env::consts::OS,
+                known_values(&OS_MAPPING)
+            )))?
+    } else {
+        os_arg.to_string()
+    };
+
+    let arch = if arch_arg == "auto" {
+        ARCH_MAPPING.get(host_arch()).map(|s| s.to_string())
+            .ok_or_else(|| MyError::LogicError(format!(
+                "Unsupported architecture: {}. Pass one of {} with --arch.",
+                host_arch(),
+                known_values(&ARCH_MAPPING)
+            )))?
+    } else {
+        arch_arg.to_string()
+    };
+
+    Ok((os, arch))
+}
+
+/// Parse a target platform such as "linux/arm64" into API (os, arch) values. Each half may be an
+/// API value ("darwin", "amd64") or Rust's name for it ("macos", "x86_64").
+pub fn parse_target(target: &str) -> Result<(String, String), MyError> {
+    let (os, arch) = target
+        .split_once('/')
+        .map(|(os, arch)| (os.trim().to_lowercase(), arch.trim().to_lowercase()))
+        .filter(|(os, arch)| !os.is_empty() && !arch.is_empty() && !arch.contains('/'))
+        .ok_or_else(|| MyError::LogicError(format!(
+            "Invalid target '{}', expected OS/ARCH (e.g. linux/arm64).",
+            target
+        )))?;
+    let lookup = |value: &str, mapping: &HashMap<&'static str, &'static str>, what: &str| {
+        if mapping.values().any(|v| *v == value) {
+            return Ok(value.to_string());
+        }
+        mapping.get(value).map(|v| v.to_string()).ok_or_else(|| MyError::LogicError(format!(
+            "Unknown {} '{}' in target '{}'. Expected one of {}.",
+            what, value, target, known_values(mapping)
+        )))
+    };
+    Ok((lookup(&os, &OS_MAPPING, "operating system")?, lookup(&arch, &ARCH_MAPPING, "architecture")?))
+}
+
+/// Resolve the download URL of a product build; `version_req` is an exact version, a
+/// constraint, or "latest"
+#[allow(clippy::too_many_arguments)]
+pub async fn get_download_url(
+    client: &reqwest::Client,
+    product: &str,
+    version_req: &str,
+    allow_prerelease: bool,
+    target_arch: &str,
+    target_os: &str,
+    license_class: &str,
+    prefer: Option<ArtifactFormat>,
+    allow_rosetta: bool,
+) -> Result<String, MyError> {
+    resolve_release(client, product, version_req, allow_prerelease, target_arch, target_os, license_class, prefer, allow_rosetta)
+        .await
+        .map(|(_, build)| build.url)
+}
+
+// Helper: whether a release is a prerelease, by its flag or a prerelease suffix on its version
+fn is_prerelease(release: &Release) -> bool {
+    release.is_prerelease || Version::parse(&release.version).is_ok_and(|v| !v.pre.is_empty())
+}
+
+// Helper: the most recently published release: the one with the latest timestamp, or the first
+// listed (the API lists the newest first) where timestamps don't tell them apart
+fn newest_published(releases: &[Release]) -> Option<&Release> {
+    releases.iter().rev().max_by_key(|r| r.created())
+}
+
+/// Sort releases by semantic version, newest first, rather than trusting the API's ordering.
+/// Releases whose version doesn't parse keep their relative order after all parsed ones.
+pub fn sort_releases_desc(releases: &mut [Release]) {
+    releases.sort_by_cached_key(|r| std::cmp::Reverse(Version::parse(&r.version).ok()));
+}
+
+/// Parse a version constraint such as ">= 1.6, < 2.0" or "~> 1.9". HashiCorp's pessimistic
+/// operator "~>" allows only the rightmost given component to increase, so it's translated into an
+/// equivalent semver range: "~> 1.9" => ">=1.9, <2.0" and "~> 1.9.3" => ">=1.9.3, <1.10.0".
+pub fn parse_version_constraint(req: &str) -> Result<VersionReq, MyError> {
+    let invalid = |detail: String| MyError::LogicError(format!("Invalid version constraint '{}': {}", req, detail));
+    let mut parts = Vec::new();
+    for part in req.split(',') {
+        let part = part.trim();
+        match part.strip_prefix("~>") {
+            Some(version) => {
+                let nums = version
+                    .trim()
+                    .split('.')
+                    .map(|n| n.parse::<u64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| invalid(e.to_string()))?;
+                let upper = match nums.as_slice() {
+                    [major] | [major, _] => format!("{}.0.0", major + 1),
+                    [major, minor, _] => format!("{}.{}.0", major, minor + 1),
+                    _ => return Err(invalid("expected MAJOR[.MINOR[.PATCH]] after '~>'".to_string())),
+                };
+                parts.push(format!(">={}", version.trim()));
+                parts.push(format!("<{}", upper));
+            }
+            None => parts.push(part.to_string()),
+        }
+    }
+    VersionReq::parse(&parts.join(", ")).map_err(|e| invalid(e.to_string()))
+}
+
+/// Parse a date such as "2024-06-01" (midnight UTC) or an RFC 3339 timestamp such as
+/// "2024-06-01T12:00:00Z"
+pub fn parse_date(value: &str) -> Result<OffsetDateTime, MyError> {
+    if let Ok(date) = Date::parse(value, time::macros::format_description!("[year]-[month]-[day]")) {
+        return Ok(date.midnight().assume_utc());
+    }
+    OffsetDateTime::parse(value, &Rfc3339).map_err(|_| MyError::LogicError(format!(
+        "Invalid date '{}'. Expected YYYY-MM-DD or an RFC 3339 timestamp (e.g. 2024-06-01T12:00:00Z).",
+        value
+    )))
+}
+
+/// Resolve the release and the platform build to download, preferring builds in `prefer`'s
+/// format when the platform has several (see `DownloaderBuilder::allow_rosetta` for `allow_rosetta`)
+#[allow(clippy::too_many_arguments)]
+pub async fn resolve_release(
+    client: &reqwest::Client,
+    product: &str,
+    version_req: &str,
+    allow_prerelease: bool,
+    target_arch: &str,
+    target_os: &str,
+    license_class: &str,
+    prefer: Option<ArtifactFormat>,
+    allow_rosetta: bool,
+) -> Result<(Release, Build), MyError> {
+    let mut builder = Downloader::builder()
+        .client(client.clone())
+        .license_class(license_class)
+        .platform(target_os, target_arch)
+        .prerelease(allow_prerelease)
+        .allow_rosetta(allow_rosetta);
+    if let Some(format) = prefer {
+        builder = builder.prefer(format);
+    }
+    builder
+        .build()?
+        .resolve(product, version_req)
+        .await
+}
+
+/// Resolve the release matching `version_req` without picking a platform build
+pub async fn resolve_version(
+    client: &reqwest::Client,
+    product: &str,
+    version_req: &str,
+    allow_prerelease: bool,
+    license_class: &str,
+) -> Result<Release, MyError> {
+    Downloader::builder()
+        .client(client.clone())
+        .license_class(license_class)
+        .prerelease(allow_prerelease)
+        .build()?
+        .release(product, version_req)
+        .await
+}
+
+/// Resolve the release matching `version_req` and return every build it publishes, whatever the
+/// platform (e.g. to mirror a release), rather than the one build `get_download_url` picks
+pub async fn get_all_builds(
+    client: &reqwest::Client,
+    product: &str,
+    version_req: &str,
+    allow_prerelease: bool,
+    license_class: &str,
+) -> Result<Vec<Build>, MyError> {
+    Downloader::builder()
+        .client(client.clone())
+        .license_class(license_class)
+        .prerelease(allow_prerelease)
+        .build()?
+        .resolve_all(product, version_req)
+        .await
+        .map(|(_, builds)| builds)
+}
+
+/// List the builds (os, arch and URL) of a product version without selecting one; the version
+/// is resolved like `get_download_url` resolves it: an exact version, a constraint, or "latest"
+pub async fn list_builds(
+    client: &reqwest::Client,
+    product: &str,
+    version_req: &str,
+    license_class: &str,
+) -> Result<Vec<Build>, MyError> {
+    Downloader::builder()
+        .client(client.clone())
+        .license_class(license_class)
+        .build()?
+        .builds(product, version_req)
+        .await
+}
+
+// --- Downloader ---
+
+/// A releases API client bound to a base URL, license class and target platform.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), hashicorp_downloader::MyError> {
+/// use hashicorp_downloader::Downloader;
+///
+/// let downloader = Downloader::builder().license_class("enterprise").build()?;
+/// let (release, _build) = downloader.resolve("vault", "~> 1.15").await?;
+/// let outcome = downloader.download(&release, "./downloads").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Downloader {
+    client: reqwest::Client,
+    base_url: String,
+    license_class: String,
+    os: String,
+    arch: String,
+    prerelease: bool,
+    only_prerelease: bool,
+    prefer: Option<ArtifactFormat>,
+    allow_rosetta: bool,
+    include_unsupported: bool,
+    released_after: Option<OffsetDateTime>,
+    released_before: Option<OffsetDateTime>,
+    overwrite: OverwritePolicy,
+    progress: bool,
+    resume: bool,
+    settings: Arc<Settings>,
+}
+
+/// Builder for [`Downloader`]. Defaults: a new `reqwest::Client` sending `USER_AGENT`,
+/// the releases API (see `set_api_url`), the "oss" license class, the host platform, no prereleases,
+/// and skipping existing files. The token, retry policy, mirrors, temp directory, rate limit,
+/// offline mode and cache policy default to the process-wide ones from the `set_*` functions.
+///
+/// `set_extract_only` and `set_preserve_mode` have no builder counterpart: they configure the
+/// extraction functions (`extract_archive`, `download_and_extract_zip`, ...), which a `Downloader`
+/// doesn't call, and stay process-wide.
+#[derive(Debug, Clone, Default)]
+pub struct DownloaderBuilder {
+    client: Option<reqwest::Client>,
+    base_url: Option<String>,
+    license_class: Option<String>,
+    os: Option<String>,
+    arch: Option<String>,
+    prerelease: bool,
+    only_prerelease: bool,
+    prefer: Option<ArtifactFormat>,
+    allow_rosetta: bool,
+    include_unsupported: bool,
+    released_after: Option<OffsetDateTime>,
+    released_before: Option<OffsetDateTime>,
+    overwrite: Option<OverwritePolicy>,
+    progress: bool,
+    resume: Option<bool>,
+    auth_token: Option<String>,
+    retry_policy: Option<RetryPolicy>,
+    mirrors: Option<Vec<String>>,
+    temp_dir: Option<PathBuf>,
+    max_rate: Option<u64>,
+    offline: Option<bool>,
+    cache_policy: Option<CachePolicy>,
+}
+
+impl DownloaderBuilder {
+    /// Use an existing client, e.g. one configured with a proxy or custom timeouts
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Override the releases API base URL, e.g. to point at a mock server or a mirror
+    /// (default: the URL given to `set_api_url`, or the public API)
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// License class to query: "oss", "enterprise" or "hcp"
+    pub fn license_class(mut self, license_class: impl Into<String>) -> Self {
+        self.license_class = Some(license_class.into());
+        self
+    }
+
+    /// Target platform in API terms (e.g. "linux", "amd64"); "auto" detects the host
+    pub fn platform(mut self, os: impl Into<String>, arch: impl Into<String>) -> Self {
+        self.os = Some(os.into());
+        self.arch = Some(arch.into());
+        self
+    }
+
+    /// Whether "latest" and version constraints may select prereleases. A stable release still
+    /// wins over the prereleases leading up to it, e.g. 1.10.0 over 1.10.0-rc1.
+    pub fn prerelease(mut self, prerelease: bool) -> Self {
+        self.prerelease = prerelease;
+        self
+    }
+
+    /// Make "latest" select the most recently published release whether it's a prerelease or
+    /// not, e.g. a release candidate published after a higher patch release. Implies
+    /// `prerelease`, so constraints and exact versions may select prereleases and stable
+    /// releases alike.
+    pub fn only_prerelease(mut self, only_prerelease: bool) -> Self {
+        self.only_prerelease = only_prerelease;
+        self
+    }
+
+    /// Artifact format to pick when the platform has several builds (default: the first listed)
+    pub fn prefer(mut self, format: ArtifactFormat) -> Self {
+        self.prefer = Some(format);
+        self
+    }
+
+    /// On darwin/arm64, fall back to the darwin/amd64 build (run via Rosetta 2) when a release
+    /// has no native one
+    pub fn allow_rosetta(mut self, allow: bool) -> Self {
+        self.allow_rosetta = allow;
+        self
+    }
+
+    /// Whether releases the API doesn't mark "supported" (e.g. deprecated ones) may be selected
+    pub fn include_unsupported(mut self, include: bool) -> Self {
+        self.include_unsupported = include;
+        self
+    }
+
+    /// Only select releases published at or after `date`
+    pub fn released_after(mut self, date: OffsetDateTime) -> Self {
+        self.released_after = Some(date);
+        self
+    }
+
+    /// Only select releases published at or before `date`, e.g. to skip brand-new ones
+    pub fn released_before(mut self, date: OffsetDateTime) -> Self {
+        self.released_before = Some(date);
+        self
+    }
+
+    /// How to handle files that already exist in the target directory
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = Some(overwrite);
+        self
+    }
+
+    /// Show a progress bar while downloading
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Resume interrupted downloads from their `.part` file (enabled by default)
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = Some(resume);
+        self
+    }
+
+    /// Send `Authorization: Bearer <token>` with API, artifact and checksum requests
+    pub fn auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// How failed and rate-limited requests are retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Mirrors to try, in order, before an artifact's original URL (see `set_mirrors` for the
+    /// URL form)
+    pub fn mirrors(mut self, mirrors: Vec<String>) -> Self {
+        self.mirrors = Some(mirrors);
+        self
+    }
+
+    /// Directory for extraction scratch space and the `.part` files of downloads in progress
+    pub fn temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = Some(dir.into());
+        self
+    }
+
+    /// Cap the combined speed of this downloader's downloads, shared with its copies, at
+    /// `bytes_per_sec`; 0 means no limit
+    pub fn max_rate(mut self, bytes_per_sec: u64) -> Self {
+        self.max_rate = Some(bytes_per_sec);
+        self
+    }
+
+    /// Never touch the network: releases come only from the response cache and downloads only
+    /// succeed for files that are already present
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = Some(offline);
+        self
+    }
+    /// Cache API responses on disk with this policy (default: the one given to
+    /// `set_cache_policy`, or no cache)
+    pub fn cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache_policy = Some(policy);
+        self
+    }
+
+    /// Build the downloader, detecting the host platform where none (or "auto") was given
+    pub fn build(self) -> Result<Downloader, MyError> {
+        let (os, arch) = resolve_platform(
+            self.os.as_deref().unwrap_or("auto"),
+            self.arch.as_deref().unwrap_or("auto"),
+        )?;
+        let base_url = match self.base_url {
+            Some(url) => normalize_api_url(&url)?,
+            None => api_url().to_string(),
+        };
+        let defaults = Settings::process_defaults();
+        let settings = Settings {
+            auth_token: self.auth_token.or(defaults.auth_token),
+            retry_policy: self.retry_policy.unwrap_or(defaults.retry_policy),
+            mirrors: match self.mirrors {
+                Some(mirrors) => normalize_mirrors(&mirrors)?,
+                None => defaults.mirrors,
+            },
+            temp_dir: self.temp_dir.or(defaults.temp_dir),
+            max_rate: match self.max_rate {
+                Some(rate) => RateLimiter::new(rate),
+                None => defaults.max_rate,
+            },
+            offline: self.offline.unwrap_or(defaults.offline),
+            cache_policy: self.cache_policy.or(defaults.cache_policy),
+        };
+        Ok(Downloader {
+            client: match self.client {
+                Some(client) => client,
+                None => reqwest::Client::builder().user_agent(USER_AGENT).build()?,
+            },
+            base_url,
+            license_class: self.license_class.unwrap_or_else(|| "oss".to_string()),
+            os,
+            arch,
+            prerelease: self.prerelease || self.only_prerelease,
+            only_prerelease: self.only_prerelease,
+            prefer: self.prefer,
+            allow_rosetta: self.allow_rosetta,
+            include_unsupported: self.include_unsupported,
+            released_after: self.released_after,
+            released_before: self.released_before,
+            overwrite: self.overwrite.unwrap_or(OverwritePolicy::Skip),
+            progress: self.progress,
+            resume: self.resume.unwrap_or(true),
+            settings: Arc::new(settings),
+        })
+    }
+}
+
+impl Downloader {
+    pub fn builder() -> DownloaderBuilder {
+        DownloaderBuilder::default()
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn license_class(&self) -> &str {
+        &self.license_class
+    }
+
+    /// Target platform as (os, arch)
+    pub fn platform(&self) -> (&str, &str) {
+        (&self.os, &self.arch)
+    }
+
+    /// A copy of this downloader that picks builds for another platform, given in the API's
+    /// spelling (see [`parse_target`])
+    pub fn with_platform(&self, os: impl Into<String>, arch: impl Into<String>) -> Downloader {
+        Downloader { os: os.into(), arch: arch.into(), ..self.clone() }
+    }
+
+    // Helper: run a request with this downloader's settings instead of the process-wide ones
+    async fn scoped<T>(&self, request: impl std::future::Future<Output = T>) -> T {
+        SETTINGS.scope(self.settings.clone(), request).await
+    }
+
+    /// Fetch the names of all products available under the license class
+    pub async fn products(&self) -> Result<Vec<String>, MyError> {
+        self.scoped(fetch_products(&self.client, &self.base_url, &self.license_class)).await
+    }
+
+    /// Fetch all releases for a product, failing if there are none
+    pub async fn releases(&self, product: &str) -> Result<Vec<Release>, MyError> {
+        self.scoped(fetch_releases_from(&self.client, &self.base_url, product, &self.license_class)).await
+    }
+
+    /// Fetch the latest supported stable release of a product, if it has one
+    pub async fn latest_release(&self, product: &str) -> Result<Option<Release>, MyError> {
+        let mut all_releases = self.releases(product).await?;
+        sort_releases_desc(&mut all_releases);
+        Ok(all_releases
+            .into_iter()
+            .find(|r| r.status.state == ReleaseState::Supported && !r.is_prerelease))
+    }
+
+    /// Check whether the latest supported stable release of a product ships a build for the platform
+    pub async fn has_build(&self, product: &str) -> Result<bool, MyError> {
+        Ok(self
+            .latest_release(product)
+            .await?
+            .map(|r| r.builds.iter().any(|b| b.os == self.os && b.arch == self.arch))
+            .unwrap_or(false))
+    }
+
+    /// Look up the latest supported stable version of each product, up to `jobs` products at once.
+    /// Products that can't be looked up are kept without a version.
+    pub async fn describe_products(&self, products: Vec<String>, jobs: usize) -> Vec<ProductInfo> {
+        // `buffered` keeps the API ordering while looking up several products at once
+        futures_util::stream::iter(products)
+            .map(|product| async move {
+                let latest_version = match self.latest_release(&product).await {
+                    Ok(release) => release.map(|r| r.version),
+                    Err(e) => {
+                        warn!("Warning: could not look up the latest version of {}: {}", product, e);
+                        None
+                    }
+                };
+                ProductInfo { name: product, latest_version }
+            })
+            .buffered(jobs.max(1))
+            .collect()
+            .await
+    }
+
+    /// Keep only the products that ship a build for the platform, checking up to `jobs` products at once
+    pub async fn products_with_build(&self, products: Vec<String>, jobs: usize) -> Vec<String> {
+        let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+        let mut set = JoinSet::new();
+        for (idx, product) in products.into_iter().enumerate() {
+            let downloader = self.clone();
+            let semaphore = semaphore.clone();
+            set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let result = downloader.has_build(&product).await;
+                (idx, product, result)
+            });
+        }
+
+        let mut matching = Vec::new();
+        while let Some(joined) = set.join_next().await {
+            match joined {
+                Ok((idx, product, Ok(true))) => matching.push((idx, product)),
+                Ok((_, _, Ok(false))) => {},
+                Ok((_, product, Err(e))) => warn!("Warning: could not check builds for {}: {}", product, e),
+                Err(e) => warn!("Warning: build check task failed: {}", e),
+            }
+        }
+        // Restore the API ordering
+        matching.sort_by_key(|(idx, _)| *idx);
+        matching.into_iter().map(|(_, product)| product).collect()
+    }
+
+    // Whether release dates are restricted (see DownloaderBuilder::released_after/released_before)
+    fn has_date_window(&self) -> bool {
+        self.released_after.is_some() || self.released_before.is_some()
+    }
+
+    // Whether a release was published within the date window; with a window set, releases
+    // without a valid timestamp are left out since they can't be placed in it
+    fn in_date_window(&self, release: &Release) -> bool {
+        if !self.has_date_window() {
+            return true;
+        }
+        release.created().is_some_and(|created| {
+            self.released_after.is_none_or(|after| created >= after)
+                && self.released_before.is_none_or(|before| created <= before)
+        })
+    }
+
+    /// Resolve the release and the platform build to download; `version_req` is an exact
+    /// version, a constraint, or "latest"
+    pub async fn resolve(&self, product: &str, version_req: &str) -> Result<(Release, Build), MyError> {
+        emit(&Event::ResolveStarted { product, version: version_req });
+        let target_release = self.release(product, version_req).await?;
+
+        // Find the build for the correct architecture and OS
+        let build = self.build_for(&target_release)?;
+        emit(&Event::Resolved {
+            product,
+            version: &target_release.version,
+            os: &build.os,
+            arch: &build.arch,
+            url: &build.url,
+        });
+
+        Ok((target_release, build))
+    }
+
+    /// Resolve the release matching `version_req` along with all of its builds, for every platform
+    pub async fn resolve_all(&self, product: &str, version_req: &str) -> Result<(Release, Vec<Build>), MyError> {
+        emit(&Event::ResolveStarted { product, version: version_req });
+        let target_release = self.release(product, version_req).await?;
+        if target_release.builds.is_empty() {
+            return Err(MyError::LogicError(format!("{} {} has no builds.", product, target_release.version)));
+        }
+        let builds = target_release.builds.clone();
+        for build in &builds {
+            emit(&Event::Resolved {
+                product,
+                version: &target_release.version,
+                os: &build.os,
+                arch: &build.arch,
+                url: &build.url,
+            });
+        }
+        Ok((target_release, builds))
+    }
+
+    /// The builds (os, arch and URL) of the release matching `version_req`, for every platform,
+    /// without selecting one
+    pub async fn builds(&self, product: &str, version_req: &str) -> Result<Vec<Build>, MyError> {
+        self.release(product, version_req).await.map(|release| release.builds)
+    }
+
+    /// Resolve the release matching `version_req` (an exact version, a constraint, or "latest"),
+    /// whatever platforms it ships
+    pub async fn release(&self, product: &str, version_req: &str) -> Result<Release, MyError> {
+        let allow_prerelease = self.prerelease;
+        // 1. Build URL and fetch all releases for the product
+        status!("Fetching releases from: {}", releases_url(&self.base_url, product, &self.license_class));
+
+        let all_releases = self.releases(product).await?;
+
+        // 2. Filter releases to find the one we want to download
+        let target_release: Release = {
+            // First, filter for only supported releases (unless all are allowed) published within
+            // the release date window, newest first
+            let (mut supported_releases, excluded): (Vec<Release>, Vec<Release>) = all_releases
+                .into_iter()
+                .partition(|r| {
+                    (self.include_unsupported || r.status.state == ReleaseState::Supported) && self.in_date_window(r)
+                });
+            // Taken before sorting, as it may need the API's ordering
+            let newest_published = newest_published(&supported_releases).map(|r| r.version.clone());
+            sort_releases_desc(&mut supported_releases);
+
+            if supported_releases.is_empty() {
+                return Err(MyError::LogicError(if self.has_date_window() {
+                    format!("No supported versions of '{}' were released within the --max-age/--min-date window.", product)
+                } else {
+                    format!("No supported versions found for '{}'. Use --include-unsupported to allow unsupported ones.", product)
+                }));
+            }
+
+            if version_req != "latest" && Version::parse(version_req).is_ok() {
+                // If a specific version is requested
+                supported_releases
+                    .into_iter()
+                    .find(|r| r.version == version_req)
+                    .ok_or_else(|| match excluded.iter().find(|r| r.version == version_req) {
+                        Some(r) if !self.in_date_window(r) => MyError::LogicError(format!(
+                            "Version '{}' was released {}, outside the --max-age/--min-date window.",
+                            version_req, r.timestamp_created.as_deref().unwrap_or("at an unknown date")
+                        )),
+                        Some(r) => MyError::LogicError(format!(
+                            "Version '{}' is {}, not supported. Use --include-unsupported to download it anyway.",
+                            version_req, r.status.state.as_str()
+                        )),
+                        None => MyError::LogicError(format!("Version '{}' not found.", version_req)),
+                    })?
+            } else if version_req != "latest" {
+                // If a version constraint is requested, pick the highest release satisfying it
+                let constraint = parse_version_constraint(version_req)?;
+                supported_releases
+                    .into_iter()
+                    .filter_map(|r| Version::parse(&r.version).ok().map(|v| (v, r)))
+                    .filter(|(v, r)| {
+                        if r.is_prerelease || !v.pre.is_empty() {
+                            // Prereleases are only eligible with --prerelease, matched on their release version
+                            allow_prerelease && constraint.matches(&Version::new(v.major, v.minor, v.patch))
+                        } else {
+                            constraint.matches(v)
+                        }
+                    })
+                    .max_by(|(a, _), (b, _)| a.cmp(b))
+                    .map(|(_, r)| r)
+                    .ok_or_else(|| MyError::LogicError(format!("No supported version satisfies '{}'.", version_req)))?
+            } else {
+                // If the latest version is requested. Releases are in semver order, where a stable
+                // release ranks above its own prereleases, so the first eligible one is the highest.
+                let mut release_iterator = supported_releases.into_iter();
+
+                if self.only_prerelease {
+                    // The newest published release, prerelease or not, even below a higher version
+                    release_iterator.find(|r| newest_published.as_ref() == Some(&r.version))
+                } else if allow_prerelease {
+                    // The highest version, with or without prerelease
+                    release_iterator.next()
+                } else {
+                    // The highest version that is not a prerelease
+                    release_iterator.find(|r| !is_prerelease(r))
+                }
+                .ok_or_else(|| MyError::LogicError(if self.only_prerelease {
+                    format!("No releases found for '{}'.", product)
+                } else {
+                    "No suitable version found. Try with --prerelease for preliminary versions.".to_string()
+                }))?
+            }
+        };
+
+        status!("Selected version: {} (Prerelease: {})", target_release.version, target_release.is_prerelease);
+        if target_release.status.state != ReleaseState::Supported {
+            warn!("Warning: {} {} is {}, not supported.", product, target_release.version, target_release.status.state.as_str());
+        }
+
+        Ok(target_release)
+    }
+
+    /// Download the release's build for the platform into `target_dir`
+    pub async fn download(&self, release: &Release, target_dir: &str) -> Result<DownloadOutcome, MyError> {
+        let build = self.build_for(release)?;
+        self.scoped(download_file(&self.client, &build.url, target_dir, self.overwrite, &PathStyle::AsIs, self.progress, self.resume))
+            .await
+    }
+
+    /// The release's build for the platform, taking the preferred format and the Rosetta 2
+    /// fallback into account
+    pub fn build_for(&self, release: &Release) -> Result<Build, MyError> {
+        let platform_builds = |arch: &str| -> Vec<&Build> {
+            release.builds.iter().filter(|b| b.os == self.os && b.arch == arch).collect()
+        };
+        let mut matches = platform_builds(&self.arch);
+
+        // Apple Silicon runs darwin/amd64 builds through Rosetta 2
+        let rosetta = matches.is_empty() && self.os == "darwin" && self.arch == "arm64" && !platform_builds("amd64").is_empty();
+        if rosetta && self.allow_rosetta {
+            status!("Note: no darwin/arm64 build for v{}, using the darwin/amd64 build, which runs via Rosetta 2.", release.version);
+            matches = platform_builds("amd64");
+        }
+
+        let first = matches.first().copied().ok_or_else(|| {
+            let available_platforms = release.platforms().join(", ");
+            let hint = if rosetta {
+                "\nA darwin/amd64 build is available, which runs via Rosetta 2: pass --allow-rosetta to use it."
+            } else {
+                ""
+            };
+            MyError::LogicError(format!(
+                "No compatible build found for platform '{}/{}'.\nAvailable platforms for v{}: {}{}",
+                self.os, self.arch, release.version, available_platforms, hint
+            ))
+        })?;
+        if matches.len() == 1 {
+            return Ok(first.clone());
+        }
+
+        // Several artifacts for the platform: take the preferred format, otherwise the first
+        let preferred = self.prefer.and_then(|format| matches.iter().copied().find(|b| format.matches(b)));
+        if let (Some(format), None) = (self.prefer, preferred) {
+            warn!("Warning: no .{} build for {}/{}, using the first available.", format.extension(), self.os, self.arch);
+        }
+        let build = preferred.unwrap_or(first);
+        let names: Vec<String> = matches.iter().map(|b| url_filename(&b.url).unwrap_or_else(|_| b.url.clone())).collect();
+        status!(
+            "Selected build: {} (available for {}/{}: {})",
+            url_filename(&build.url).unwrap_or_else(|_| build.url.clone()),
+            self.os,
+            self.arch,
+            names.join(", ")
+        );
+        Ok(build.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::write::FileOptions;
+
+    // A fresh directory under the system temp dir, removed again when dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("hcd-test-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TestDir(path)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    enum Entry<'a> {
+        File(&'a str, &'a [u8], u32),
+        Symlink(&'a str, &'a str),
+    }
+
+    // An in-memory ZIP with the given entries, names taken verbatim
+    fn zip_bytes(entries: &[Entry]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for entry in entries {
+            match entry {
+                Entry::File(name, data, mode) => {
+                    writer.start_file(*name, FileOptions::default().unix_permissions(*mode)).unwrap();
+                    writer.write_all(data).unwrap();
+                }
+                Entry::Symlink(name, target) => writer.add_symlink(*name, *target, FileOptions::default()).unwrap(),
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn repair_skips_symlink_entries() {
+        let dir = TestDir::new("repair-symlink");
+        let zip_path = dir.0.join("product.zip");
+        let dest = dir.0.join("out");
+        std::fs::write(&zip_path, zip_bytes(&[
+            Entry::Symlink("terraform", "/etc/passwd"),
+            Entry::File("vault", b"\x7fELF vault", 0o755),
+        ]))
+        .unwrap();
+        assert_eq!(repair_from_zip(&zip_path, &dest).await.unwrap(), 1);
+        assert!(dest.join("vault").is_file());
+        assert!(!dest.join("terraform").exists());
+    }
+
+    fn delays(strategy: BackoffStrategy, random: f64) -> Vec<u64> {
+        let policy = RetryPolicy {
+            strategy,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(3),
+            ..RetryPolicy::default()
+        };
+        (0..5).map(|attempt| policy.delay_with(attempt, random).as_millis() as u64).collect()
+    }
+
+    #[test]
+    fn fixed_backoff_repeats_the_base_delay() {
+        assert_eq!(delays(BackoffStrategy::Fixed, 0.7), [500, 500, 500, 500, 500]);
+    }
+
+    #[test]
+    fn exponential_backoff_doubles_up_to_the_max() {
+        assert_eq!(delays(BackoffStrategy::Exponential, 0.7), [500, 1000, 2000, 3000, 3000]);
+    }
+
+    #[test]
+    fn jittered_backoff_stays_between_half_and_all_of_the_capped_delay() {
+        assert_eq!(delays(BackoffStrategy::ExponentialJitter, 0.0), [250, 500, 1000, 1500, 1500]);
+        assert_eq!(delays(BackoffStrategy::ExponentialJitter, 0.5), [375, 750, 1500, 2250, 2250]);
+        for attempt in 0..20 {
+            let delay = RetryPolicy::default().delay(attempt);
+            assert!(delay <= RetryPolicy::default().max_delay);
+        }
+    }
+
+    #[test]
+    fn backoff_max_also_caps_the_base_delay() {
+        let policy = RetryPolicy { strategy: BackoffStrategy::Fixed, max_delay: Duration::from_millis(100), ..RetryPolicy::default() };
+        assert_eq!(policy.delay_with(0, 0.0), Duration::from_millis(100));
+    }
+
+    // Entries that try to leave the extraction directory, next to one legitimate executable
+    fn zip_slip_entries(outside: &str) -> Vec<u8> {
+        zip_bytes(&[
+            Entry::File("../evil", b"#!/bin/sh\necho evil\n", 0o755),
+            Entry::File("bin/../../evil-nested", b"#!/bin/sh\necho evil\n", 0o755),
+            Entry::File(outside, b"#!/bin/sh\necho evil\n", 0o755),
+            Entry::Symlink("evil-link", "../../evil-target"),
+            Entry::File("terraform", b"\x7fELF terraform", 0o755),
+        ])
+    }
+
+    #[test]
+    fn zip_symlinks_are_recognized_by_mode() {
+        assert!(is_zip_symlink(Some(0o120777)));
+        assert!(!is_zip_symlink(Some(0o100755)));
+        assert!(!is_zip_symlink(None));
+    }
+
+    #[test]
+    fn zip_slip_entries_are_skipped_by_the_internal_extractor() {
+        let dir = TestDir::new("zip-slip-internal");
+        let dest = dir.0.join("root").join("dest");
+        let outside = dir.0.join("absolute-evil");
+        let zip = zip_slip_entries(outside.to_str().unwrap());
+        std::fs::create_dir_all(&dest).unwrap();
+        let extracted = extract_exes_from_archive(Cursor::new(zip), &dest, OverwritePolicy::Overwrite).unwrap();
+        assert_eq!(extracted, 1);
+        let names: Vec<_> = std::fs::read_dir(&dest).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, ["terraform"]);
+        assert!(!dir.0.join("root").join("evil").exists());
+        assert!(!dir.0.join("evil-nested").exists());
+        assert!(!outside.exists());
+    }
+
+    #[tokio::test]
+    async fn unsafe_zip_entries_are_detected() {
+        let dir = TestDir::new("zip-slip-detect");
+        let check = |name: &str, entries: &[Entry]| {
+            let path = dir.0.join(name);
+            std::fs::write(&path, zip_bytes(entries)).unwrap();
+            path
+        };
+        let parent = check("parent.zip", &[Entry::File("../evil", b"x", 0o755)]);
+        let absolute = check("absolute.zip", &[Entry::File("/tmp/evil", b"x", 0o755)]);
+        let symlink = check("symlink.zip", &[Entry::Symlink("terraform", "/etc/passwd")]);
+        let clean = check("clean.zip", &[Entry::File("terraform", b"x", 0o755)]);
+        assert_eq!(unsafe_zip_entry(&parent).await.unwrap().as_deref(), Some("../evil"));
+        assert_eq!(unsafe_zip_entry(&absolute).await.unwrap().as_deref(), Some("/tmp/evil"));
+        assert_eq!(unsafe_zip_entry(&symlink).await.unwrap().as_deref(), Some("terraform"));
+        assert_eq!(unsafe_zip_entry(&clean).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn zip_slip_archives_write_nothing_outside_dest_dir() {
+        let dir = TestDir::new("zip-slip-extract");
+        let root = dir.0.join("root");
+        let dest = root.join("dest");
+        let outside = dir.0.join("absolute-evil");
+        let zip_path = root.join("product.zip");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(&zip_path, zip_slip_entries(outside.to_str().unwrap())).unwrap();
+        let extracted = extract_exe_from_zip(&zip_path, &dest, OverwritePolicy::Overwrite).await.unwrap();
+        assert_eq!(extracted, 1);
+        assert!(dest.join("terraform").is_file());
+        assert!(!root.join("evil").exists());
+        assert!(!dir.0.join("evil-nested").exists());
+        assert!(!dest.join("evil-link").exists());
+        assert!(!outside.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn moving_extracted_files_does_not_follow_symlinks_out_of_the_root() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        let dir = TestDir::new("move-symlinked-dir");
+        let (src, dest, outside) = (dir.0.join("src"), dir.0.join("dest"), dir.0.join("outside"));
+        for path in [&src, &dest, &outside] {
+            std::fs::create_dir_all(path).unwrap();
+        }
+        for exe in [src.join("terraform"), outside.join("victim")] {
+            std::fs::write(&exe, b"\x7fELF").unwrap();
+            std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        symlink(&outside, src.join("bin")).unwrap();
+        symlink(outside.join("victim"), src.join("victim-link")).unwrap();
+
+        assert_eq!(move_exes_recursively(&src, &dest, OverwritePolicy::Overwrite).unwrap(), 1);
+        let moved: Vec<_> = std::fs::read_dir(&dest).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(moved, ["terraform"]);
+        assert!(outside.join("victim").is_file());
+    }
+
+    // A Linux build as some archivers store it: no extension and no execute bits, next to files
+    // that aren't executables
+    fn bare_binary_zip() -> Vec<u8> {
+        zip_bytes(&[
+            Entry::File("terraform", b"\x7fELF\x02\x01\x01 terraform", 0o644),
+            Entry::File("LICENSE.txt", b"Business Source License", 0o644),
+            Entry::File("README", b"terraform", 0o644),
+        ])
+    }
+
+    #[test]
+    fn bare_binaries_are_extracted_by_the_internal_extractor() {
+        let dir = TestDir::new("bare-internal");
+        let extracted = extract_exes_from_archive(Cursor::new(bare_binary_zip()), &dir.0, OverwritePolicy::Overwrite).unwrap();
+        assert_eq!(extracted, 1);
+        assert_eq!(std::fs::read(dir.0.join("terraform")).unwrap(), b"\x7fELF\x02\x01\x01 terraform");
+        assert!(!dir.0.join("LICENSE.txt").exists());
+        assert!(!dir.0.join("README").exists());
+    }
+
+    #[tokio::test]
+    async fn bare_binaries_are_extracted_from_zip_files() {
+        let dir = TestDir::new("bare-zip");
+        let (zip_path, dest) = (dir.0.join("terraform.zip"), dir.0.join("dest"));
+        std::fs::write(&zip_path, bare_binary_zip()).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        assert_eq!(extract_exe_from_zip(&zip_path, &dest, OverwritePolicy::Overwrite).await.unwrap(), 1);
+        assert!(dest.join("terraform").is_file());
+        assert!(!dest.join("README").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn extracted_binaries_are_executable() {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode();
+        let dir = TestDir::new("exec-bit");
+        let (zip_path, internal, external) = (dir.0.join("terraform.zip"), dir.0.join("internal"), dir.0.join("external"));
+        std::fs::write(&zip_path, bare_binary_zip()).unwrap();
+        for dest in [&internal, &external] {
+            std::fs::create_dir_all(dest).unwrap();
+        }
+
+        extract_exes_from_archive(Cursor::new(bare_binary_zip()), &internal, OverwritePolicy::Overwrite).unwrap();
+        extract_exe_from_zip(&zip_path, &external, OverwritePolicy::Overwrite).await.unwrap();
+        for dest in [&internal, &external] {
+            let mode = mode(&dest.join("terraform"));
+            assert!(mode & 0o111 != 0, "{} has mode {:o}", dest.display(), mode);
+        }
+    }
+
+    // Helper: read a request's head off a mock server connection, returning its path
+    async fn read_request_path(stream: &mut tokio::net::TcpStream) -> String {
+        use tokio::io::AsyncReadExt;
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+        String::from_utf8_lossy(&request).split_whitespace().nth(1).unwrap_or("/").to_string()
+    }
+
+    // A local HTTP server answering every request with `respond(path)`, as extra header lines and a
+    // body (JSON unless the headers say otherwise). Returns its base URL.
+    async fn mock_server<B: Into<Vec<u8>>>(respond: impl Fn(&str) -> (String, B) + Send + 'static) -> String {
+        use tokio::io::AsyncWriteExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let path = read_request_path(&mut stream).await;
+                let (headers, body) = respond(&path);
+                let body: Vec<u8> = body.into();
+                let content_type = if headers.contains("Content-Type") { "" } else { "Content-Type: application/json\r\n" };
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\nConnection: close\r\n{}\r\n",
+                    content_type,
+                    body.len(),
+                    headers
+                );
+                let _ = stream.write_all(head.as_bytes()).await;
+                let _ = stream.write_all(&body).await;
+            }
+        });
+        base
+    }
+
+    // Versions in no particular order, as a mirror or an older API might list them
+    const SHUFFLED_VERSIONS: [&str; 9] =
+        ["1.9.3", "1.10.0-beta1", "0.15.5", "1.10.0", "2.0.0-alpha1", "1.2.0", "1.10.0-rc1", "1.9.10", "2.0.0-alpha2"];
+
+    fn release(version: &str) -> Release {
+        serde_json::from_value(serde_json::json!({
+            "version": version,
+            "status": { "state": "supported" },
+            "is_prerelease": version.contains('-'),
+            "builds": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn releases_sort_by_semver_newest_first() {
+        let mut releases: Vec<Release> = SHUFFLED_VERSIONS.iter().map(|v| release(v)).collect();
+        sort_releases_desc(&mut releases);
+        let sorted: Vec<&str> = releases.iter().map(|r| r.version.as_str()).collect();
+        assert_eq!(
+            sorted,
+            ["2.0.0-alpha2", "2.0.0-alpha1", "1.10.0", "1.10.0-rc1", "1.10.0-beta1", "1.9.10", "1.9.3", "1.2.0", "0.15.5"]
+        );
+    }
+
+    #[test]
+    fn unparsable_versions_sort_last_in_their_original_order() {
+        let mut releases: Vec<Release> = ["nightly", "1.0.0", "dev", "1.1.0"].iter().map(|v| release(v)).collect();
+        sort_releases_desc(&mut releases);
+        let sorted: Vec<&str> = releases.iter().map(|r| r.version.as_str()).collect();
+        assert_eq!(sorted, ["1.1.0", "1.0.0", "nightly", "dev"]);
+    }
+
+    // A downloader for a mock API serving SHUFFLED_VERSIONS for every product
+    async fn shuffled_downloader() -> DownloaderBuilder {
+        let body = serde_json::to_string(&SHUFFLED_VERSIONS.map(release)).unwrap();
+        let base = mock_server(move |_| (String::new(), body.clone())).await;
+        Downloader::builder()
+            .base_url(format!("{}/v1/", base))
+            .retry_policy(RetryPolicy { retries: 0, ..RetryPolicy::default() })
+    }
+
+    #[tokio::test]
+    async fn latest_is_the_highest_stable_version() {
+        let downloader = shuffled_downloader().await.build().unwrap();
+        assert_eq!(downloader.release("terraform", "latest").await.unwrap().version, "1.10.0");
+        assert_eq!(downloader.release("terraform", "~> 1.9.0").await.unwrap().version, "1.9.10");
+    }
+
+    #[tokio::test]
+    async fn latest_with_prereleases_is_the_highest_version() {
+        let downloader = shuffled_downloader().await.prerelease(true).build().unwrap();
+        assert_eq!(downloader.release("terraform", "latest").await.unwrap().version, "2.0.0-alpha2");
+    }
+
+    #[test]
+    fn newest_published_goes_by_timestamp_then_api_order() {
+        let dated = |version: &str, created: Option<&str>| Release { timestamp_created: created.map(str::to_string), ..release(version) };
+        let releases = [
+            dated("1.9.3", Some("2024-07-01T00:00:00Z")),
+            dated("1.10.0-rc1", Some("2024-09-01T00:00:00Z")),
+            dated("1.10.0-beta1", None),
+            dated("1.9.4", Some("2024-08-01T00:00:00Z")),
+        ];
+        assert_eq!(newest_published(&releases).unwrap().version, "1.10.0-rc1");
+        let undated: Vec<Release> = SHUFFLED_VERSIONS.iter().map(|v| release(v)).collect();
+        assert_eq!(newest_published(&undated).unwrap().version, SHUFFLED_VERSIONS[0]);
+        assert!(newest_published(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn latest_with_only_prereleases_is_the_newest_published_release() {
+        let releases = serde_json::json!([
+            { "version": "1.9.4", "status": { "state": "supported" }, "is_prerelease": false, "builds": [],
+              "timestamp_created": "2024-09-10T00:00:00Z" },
+            { "version": "1.10.0-rc1", "status": { "state": "supported" }, "is_prerelease": true, "builds": [],
+              "timestamp_created": "2024-09-01T00:00:00Z" },
+            { "version": "1.9.3", "status": { "state": "supported" }, "is_prerelease": false, "builds": [],
+              "timestamp_created": "2024-07-01T00:00:00Z" },
+        ])
+        .to_string();
+        let base = mock_server(move |_| (String::new(), releases.clone())).await;
+        let builder = Downloader::builder()
+            .base_url(format!("{}/v1/", base))
+            .retry_policy(RetryPolicy { retries: 0, ..RetryPolicy::default() });
+        let only_prerelease = builder.clone().only_prerelease(true).build().unwrap();
+        assert_eq!(only_prerelease.release("terraform", "latest").await.unwrap().version, "1.9.4");
+        // Constraints and exact versions treat prereleases and stable releases alike, as "latest" does
+        assert_eq!(only_prerelease.release("terraform", "~> 1.9").await.unwrap().version, "1.10.0-rc1");
+        assert_eq!(only_prerelease.release("terraform", "~> 1.9.0").await.unwrap().version, "1.9.4");
+        assert_eq!(only_prerelease.release("terraform", "1.9.3").await.unwrap().version, "1.9.3");
+        let prerelease = builder.prerelease(true).build().unwrap();
+        assert_eq!(prerelease.release("terraform", "latest").await.unwrap().version, "1.10.0-rc1");
+    }
+
+    #[test]
+    fn url_filenames_ignore_queries_and_fragments() {
+        let cases = [
+            ("https://x/terraform_1.0.zip", "terraform_1.0.zip"),
+            ("https://x/terraform_1.0.zip?X-Amz-Signature=abc123&X-Amz-Expires=300", "terraform_1.0.zip"),
+            ("https://x/terraform_1.0.zip#sha256=0f1e2d", "terraform_1.0.zip"),
+            ("https://x/terraform/1.0/terraform_1.0.zip?token=a%2Fb#frag", "terraform_1.0.zip"),
+            ("https://x/terraform_1.0.zip?next=/other/file.zip", "terraform_1.0.zip"),
+            ("https://x/terraform/1.0/?download=1", "1.0"),
+            ("https://x/dl?file=terraform_1.0.zip", "dl"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(url_filename(url).unwrap(), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn url_filenames_must_name_a_file() {
+        for url in ["https://x/", "https://x/?file=terraform.zip", "https://x#terraform.zip", "https://x/a/..", "not a url"] {
+            assert!(url_filename(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn next_links_are_parsed_from_link_headers() {
+        let url = reqwest::Url::parse("https://api.example.com/v1/products?license_class=oss").unwrap();
+        let next = |values: &[&str]| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            for value in values {
+                headers.append(reqwest::header::LINK, value.parse().unwrap());
+            }
+            next_link(&url, &headers)
+        };
+        let page2 = Some("https://api.example.com/v1/products?page=2".to_string());
+        assert_eq!(next(&[r#"<https://api.example.com/v1/products?page=2>; rel="next""#]), page2);
+        assert_eq!(next(&["</v1/products?page=2>; rel=next"]), page2);
+        assert_eq!(next(&["<products?page=2>; REL=\"Next\""]), page2);
+        assert_eq!(next(&[r#"</v1/products?page=1>; rel="prev", </v1/products?page=2>; rel="next""#]), page2);
+        assert_eq!(next(&[r#"</v1/products?page=1>; rel="prev""#, r#"</v1/products?page=2>; title="more"; rel="last next""#]), page2);
+        assert_eq!(next(&[r#"</v1/products?page=9>; rel="last""#]), None);
+        assert_eq!(next(&["/v1/products?page=2; rel=next", "garbage"]), None);
+        assert_eq!(next(&[]), None);
+    }
+
+    // A mock product list spread over pages that link to each other, by Link header or cursor
+    async fn paged_products(path: &'static str) -> String {
+        mock_server(|path| match path {
+            "/v1/products?license_class=oss" => ("Link: </v1/products?page=2>; rel=\"next\"\r\n".into(), r#"["boundary","consul"]"#.to_string()),
+            "/v1/products?page=2" => ("Link: <page3>; rel=\"next\"\r\n".into(), r#"["nomad"]"#.to_string()),
+            "/v1/page3" => (String::new(), r#"["terraform"]"#.to_string()),
+            "/v1/cursor" => (String::new(), r#"{"products":["packer"],"next_cursor":"abc"}"#.to_string()),
+            "/v1/cursor?after=abc" => (String::new(), r#"{"products":["vault"],"next_cursor":""}"#.to_string()),
+            _ => ("Link: </v1/loop>; rel=\"next\"\r\n".into(), r#"["waypoint"]"#.to_string()),
+        })
+        .await
+            + path
+    }
+
+    #[tokio::test]
+    async fn product_pages_are_concatenated() {
+        let client = reqwest::Client::new();
+        let linked = fetch_product_pages(&client, &paged_products("/v1/products?license_class=oss").await).await.unwrap();
+        assert_eq!(linked, serde_json::json!(["boundary", "consul", "nomad", "terraform"]));
+        let cursor = fetch_product_pages(&client, &paged_products("/v1/cursor").await).await.unwrap();
+        assert_eq!(cursor, serde_json::json!(["packer", "vault"]));
+    }
+
+    #[tokio::test]
+    async fn looping_product_pages_fail() {
+        let err = fetch_product_pages(&reqwest::Client::new(), &paged_products("/v1/loop").await).await.unwrap_err();
+        assert!(err.to_string().contains("loops back"), "{}", err);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_paces_bytes_to_the_limit() {
+        let limiter = RateLimiter::new(1000).unwrap();
+        let start = tokio::time::Instant::now();
+        for _ in 0..10 {
+            limiter.consume(500).await;
+        }
+        // 5000 bytes at 1000 bytes/s
+        assert!(start.elapsed() >= Duration::from_secs(5), "{:?}", start.elapsed());
+        assert!(start.elapsed() < Duration::from_secs(6), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_is_shared_by_concurrent_downloads() {
+        let limiter = RateLimiter::new(1000).unwrap();
+        let start = tokio::time::Instant::now();
+        let download = |limiter: Arc<RateLimiter>| async move {
+            for _ in 0..5 {
+                limiter.consume(400).await;
+            }
+        };
+        tokio::join!(download(limiter.clone()), download(limiter.clone()), download(limiter));
+        // 3 x 2000 bytes, combined, at 1000 bytes/s
+        assert!(start.elapsed() >= Duration::from_secs(6), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn zero_rate_means_no_limit() {
+        assert!(RateLimiter::new(0).is_none());
+    }
+
+    // A local HTTP server sending the first `split` bytes of `body` to its one client, and the rest
+    // only once `gate` fires. Returns its base URL.
+    async fn gated_server(body: Vec<u8>, split: usize, gate: tokio::sync::oneshot::Receiver<()>) -> String {
+        use tokio::io::AsyncWriteExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_request_path(&mut stream).await;
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body[..split]).await.unwrap();
+            let _ = gate.await;
+            stream.write_all(&body[split..]).await.unwrap();
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn downloads_are_written_to_disk_as_they_arrive() {
+        const HALF: usize = 4 * 1024 * 1024;
+        let body: Vec<u8> = (0..2 * HALF).map(|i| (i % 251) as u8).collect();
+        let (release, gate) = tokio::sync::oneshot::channel();
+        let url = format!("{}/vault_1.17.2+ent_linux_amd64.zip", gated_server(body.clone(), HALF, gate).await);
+        let dir = TestDir::new("streamed-download");
+        let dest = dir.0.join("vault.zip");
+        let download = tokio::spawn({
+            let dest = dest.clone();
+            async move {
+                let client = reqwest::Client::new();
+                download_to(&client, &url, &dest, OverwritePolicy::Overwrite, &PathStyle::AsIs, false, false).await
+            }
+        });
+
+        // The first half has to reach the disk while the server holds back the rest; a download
+        // that buffers the whole body in memory would write nothing before the body ends
+        let part = part_path_for(&dest);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        while std::fs::metadata(&part).map_or(0, |m| m.len()) < HALF as u64 {
+            assert!(tokio::time::Instant::now() < deadline, "nothing was written before the body finished");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        release.send(()).unwrap();
+
+        let outcome = download.await.unwrap().unwrap();
+        assert_eq!(outcome.bytes, body.len() as u64);
+        assert!(!outcome.skipped);
+        assert!(std::fs::read(&dest).unwrap() == body, "the downloaded file differs from the body");
+        assert!(!part.exists());
+    }
+
+    // A mock server serving bare_binary_zip() as a ZIP file at every path
+    async fn zip_server() -> String {
+        mock_server(|_| ("Content-Type: application/zip\r\n".to_string(), bare_binary_zip())).await
+    }
+
+    #[tokio::test]
+    async fn stream_extraction_spools_archives_over_the_memory_limit_to_disk() {
+        let url = format!("{}/terraform_1.0_linux_amd64.zip", zip_server().await);
+        let client = reqwest::Client::new();
+        let sha256 = format!("{:x}", Sha256::digest(bare_binary_zip()));
+
+        let (spool, bytes, actual) = fetch_spooled(&client, &url, true, "terraform.zip", false, 1 << 20).await.unwrap();
+        assert!(matches!(spool, Spool::Memory(ref buffered) if buffered.len() as u64 == bytes));
+        assert_eq!(actual, sha256);
+
+        let (spool, bytes, actual) = fetch_spooled(&client, &url, true, "terraform.zip", false, 16).await.unwrap();
+        let Spool::File { scratch, path, file } = spool else { panic!("a body over the limit was kept in memory") };
+        drop(file);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), bytes);
+        assert_eq!(actual, sha256);
+        let dir = TestDir::new("stream-spooled");
+        assert_eq!(extract_archive(&path, &dir.0, OverwritePolicy::Overwrite).await.unwrap(), 1);
+        drop(scratch);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn stream_extraction_survives_a_bogus_content_length() {
+        use tokio::io::AsyncWriteExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/terraform.zip", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_request_path(&mut stream).await;
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", u64::MAX / 2);
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(b"PK\x03\x04 not nearly that long").await;
+        });
+        let dir = TestDir::new("stream-bogus-length");
+        let client = reqwest::Client::new();
+        let result = SETTINGS
+            .scope(
+                Arc::new(Settings { retry_policy: RetryPolicy { retries: 0, ..RetryPolicy::default() }, ..Settings::default() }),
+                download_and_extract_zip(&client, &url, &dir.0, OverwritePolicy::Overwrite, None, false),
+            )
+            .await;
+        assert!(result.is_err());
+        assert!(!dir.0.join("terraform").exists());
+    }
+
+    #[tokio::test]
+    async fn stream_extraction_tries_mirrors_first() {
+        let settings = Settings { mirrors: vec![zip_server().await], ..Settings::default() };
+        let dir = TestDir::new("stream-mirror");
+        let client = reqwest::Client::new();
+        // Nothing listens on port 1, so only the mirror can serve the archive
+        let url = "http://127.0.0.1:1/terraform/1.0/terraform_1.0_linux_amd64.zip";
+        let count = SETTINGS
+            .scope(Arc::new(settings), download_and_extract_zip(&client, url, &dir.0, OverwritePolicy::Overwrite, None, false))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(dir.0.join("terraform").is_file());
+    }
+
+    #[tokio::test]
+    async fn downloaders_keep_their_own_settings() {
+        // Nothing listens on port 1, so only an offline downloader fails without connecting
+        let builder = Downloader::builder()
+            .base_url("http://127.0.0.1:1/v1")
+            .retry_policy(RetryPolicy { retries: 0, ..RetryPolicy::default() });
+        let offline = builder.clone().offline(true).temp_dir("/tmp/hcd-offline").build().unwrap();
+        let online = builder.build().unwrap();
+
+        let err = offline.releases("terraform").await.unwrap_err().to_string();
+        assert!(err.contains("Offline mode"), "{}", err);
+        let err = online.releases("terraform").await.unwrap_err().to_string();
+        assert!(!err.contains("Offline mode"), "{}", err);
+
+        assert_eq!(offline.scoped(async { temp_dir() }).await, PathBuf::from("/tmp/hcd-offline"));
+        assert_eq!(online.scoped(async { temp_dir() }).await, std::env::temp_dir());
+        assert_eq!(temp_dir(), std::env::temp_dir());
+    }
+
+    #[tokio::test]
+    async fn downloaders_keep_their_own_cache_policy() {
+        let dir = TestDir::new("downloader-cache");
+        let base_url = "http://127.0.0.1:1/v1/";
+        let releases = serde_json::json!([{ "version": "1.9.3", "status": { "state": "supported" }, "is_prerelease": false, "builds": [] }]);
+        write_cache(&dir.0.join("releases-oss-terraform.json"), &releases_url(base_url, "terraform", "oss"), &releases)
+            .await
+            .unwrap();
+
+        let builder = Downloader::builder().base_url(base_url).offline(true);
+        let policy = CachePolicy { dir: dir.0.clone(), ttl: Duration::from_secs(60), refresh: false };
+        let cached = builder.clone().cache_policy(policy).build().unwrap();
+        let uncached = builder.build().unwrap();
+
+        assert_eq!(cached.releases("terraform").await.unwrap()[0].version, "1.9.3");
+        let err = uncached.releases("terraform").await.unwrap_err().to_string();
+        assert!(err.contains("no cached response"), "{}", err);
+    }
+
+    #[test]
+    fn downloader_mirrors_are_validated() {
+        let err = Downloader::builder().mirrors(vec!["ftp://mirror.example.com".to_string()]).build().unwrap_err();
+        assert!(err.to_string().contains("Invalid mirror URL"), "{}", err);
+        assert!(Downloader::builder().mirrors(vec!["https://mirror.example.com/hashicorp/".to_string()]).build().is_ok());
+    }
+}